@@ -0,0 +1,84 @@
+//! Latency of [KRunnerPlugin::matches] over 1 to 200 generated Dash doc sets, for keyword
+//! completion (`benc`) and a full search of every doc set (`bench vec`). Compare runs with
+//! `cargo bench -- --save-baseline <name>` and `--baseline <name>`.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::SqlitePool;
+use tempfile::TempDir;
+
+use kdashdoc::config::Config;
+use kdashdoc::dash::Dash;
+use kdashdoc::krunner::{KRunnerPlugin, QueryMode};
+use kdashdoc::provider::DynProvider;
+
+const DOC_SET_COUNTS: [usize; 4] = [1, 10, 50, 200];
+const ENTRIES_PER_DOC_SET: usize = 2000;
+
+/// Writes the doc set `Bench<i>` under `root`, every one answering to the keyword `bench`.
+async fn doc_set(root: &Path, i: usize) {
+    let path = root.join(format!("Bench{i}.docset"));
+    let resources = path.join("Contents").join("Resources");
+    std::fs::create_dir_all(&resources).unwrap();
+    std::fs::write(
+        path.join("meta.json"),
+        format!(r#"{{"name": "Bench{i}", "extra": {{"keywords": ["bench"]}}}}"#),
+    ).unwrap();
+
+    let options = SqliteConnectOptions::new()
+        .filename(resources.join("docSet.dsidx"))
+        .create_if_missing(true);
+    let db = SqlitePool::connect_with(options).await.unwrap();
+    let statements = [
+        "CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT)".to_string(),
+        "CREATE UNIQUE INDEX anchor ON searchIndex (name, type, path)".to_string(),
+        format!(r"
+            WITH RECURSIVE n(i) AS (SELECT 0 UNION ALL SELECT i + 1 FROM n WHERE i < {})
+            INSERT INTO searchIndex(name, type, path)
+            SELECT
+                CASE i % 4 WHEN 0 THEN 'Vec' WHEN 1 THEN 'String' WHEN 2 THEN 'HashMap' ELSE 'Iterator' END || i,
+                CASE i % 3 WHEN 0 THEN 'Struct' WHEN 1 THEN 'Method' ELSE 'Function' END,
+                'page' || i || '.html'
+            FROM n
+        ", ENTRIES_PER_DOC_SET - 1),
+    ];
+    for statement in statements {
+        sqlx::query(&statement).execute(&db).await.unwrap();
+    }
+    db.close().await;
+}
+
+async fn plugin(root: &Path, count: usize) -> KRunnerPlugin {
+    for i in 0..count {
+        doc_set(root, i).await;
+    }
+    let mut config = Config::default();
+    config.dash.root = Some(root.to_path_buf());
+    config.dash.cache_metadata = false;
+    config.search.sort_completions_by_usage = false;
+    // Measure the whole search rather than what makes it before the soft deadline
+    config.search.timeout_ms = 60_000;
+    let dash = Arc::new(Dash::new(&config.dash).await.unwrap()) as DynProvider;
+    KRunnerPlugin::new(vec![dash], Arc::new(config)).unwrap()
+}
+
+fn queries(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let mut group = c.benchmark_group("query");
+    for count in DOC_SET_COUNTS {
+        let dir = TempDir::new().unwrap();
+        let plugin = runtime.block_on(plugin(dir.path(), count));
+        for (name, query) in [("completion", "benc"), ("search", "bench vec")] {
+            group.bench_with_input(BenchmarkId::new(name, count), query, |b, query| {
+                b.to_async(&runtime).iter(|| plugin.matches(query, QueryMode::Normal));
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, queries);
+criterion_main!(benches);
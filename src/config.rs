@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::provider::EntryType;
+
+/// User settings read from `~/.config/kdashdoc/config.toml`. Every key is optional, a missing
+/// file or key behaves like a stock install.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// `env_logger` filter used when neither `--log-level` nor `RUST_LOG` is given, e.g. `debug`
+    /// or `info,kdashdoc::dash=trace`.
+    pub log_level: String,
+    /// Exit after this many minutes without a query, D-Bus activation starts the service again
+    /// on the next one. 0 keeps it running.
+    pub idle_timeout_minutes: u64,
+    /// Names of the enabled providers, see `registry::PROVIDERS` for the available ones.
+    pub providers: Vec<String>,
+    pub search: SearchConfig,
+    pub dash: DashConfig,
+    pub feeds: FeedsConfig,
+    pub http: HttpConfig,
+    pub info: InfoConfig,
+    pub markdown: MarkdownConfig,
+    pub devdocs: DevDocsConfig,
+    pub pydoc: PyDocConfig,
+    pub godoc: GoDocConfig,
+    /// Icon names by entry type, e.g. `Guide = "help-contents"`, replacing the built-in ones.
+    pub icons: HashMap<String, String>,
+    pub entry_icon_mode: EntryIconMode,
+    pub group_by: GroupBy,
+    pub highlight_mode: HighlightMode,
+    /// Import statements copied by the "Copy import" action, by doc set name (`Python_3`) or
+    /// title (`Python 3`). See `imports::import_snippet` for the placeholders; doc sets without
+    /// one copy the plain name.
+    pub import_templates: HashMap<String, String>,
+    /// Namespace prefixes hidden from displayed titles, by doc set name, e.g.
+    /// `Android = { prefix = "android.widget." }` or `Java = "common"`.
+    pub trim_prefixes: HashMap<String, TrimPrefix>,
+    /// Doc set keywords by window class, e.g. `kate = "rust"`, searched by queries starting with
+    /// `SearchConfig::focused_prefix` while that application had focus.
+    pub window_keywords: HashMap<String, String>,
+}
+
+/// What to trim off the titles of a doc set's entries. The full name stays in the subtext and
+/// everything acting on the entry.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrimPrefix {
+    /// This exact prefix, on the titles starting with it.
+    Prefix(String),
+    /// The namespace shared by every result the doc set returned for the query.
+    Common,
+}
+
+/// Which icon doc entries are shown with.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryIconMode {
+    /// The icon of the entry's type, see `Config::icons`.
+    #[default]
+    Type,
+    /// The icon of the doc set the entry belongs to.
+    DocSet,
+    /// The doc set's icon, or the type's icon for doc sets without one.
+    Both,
+}
+
+/// How the part of a title matching the query is pointed out.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightMode {
+    #[default]
+    None,
+    /// In bold, for themes rendering markup in match text.
+    Markup,
+    /// Spelled out after the subtext, e.g. `Vector · matched: vec`.
+    Subtext,
+}
+
+/// What KRunner groups doc entries under, i.e. their category.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// The name of the doc set the entry belongs to.
+    #[default]
+    DocSet,
+    /// The entry's type, so e.g. functions of every doc set are listed together.
+    Type,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            log_level: "info".into(),
+            idle_timeout_minutes: 0,
+            providers: vec!["Dash".into()],
+            search: Default::default(),
+            dash: Default::default(),
+            feeds: Default::default(),
+            http: Default::default(),
+            info: Default::default(),
+            markdown: Default::default(),
+            devdocs: Default::default(),
+            pydoc: Default::default(),
+            godoc: Default::default(),
+            icons: Default::default(),
+            entry_icon_mode: Default::default(),
+            group_by: Default::default(),
+            highlight_mode: Default::default(),
+            import_templates: [
+                ("Rust", "use {name};"),
+                ("Python_3", "from {module} import {last}"),
+                ("Python_2", "from {module} import {last}"),
+                ("Java_SE8", "import {name};"),
+                ("Java_SE11", "import {name};"),
+                ("Java_SE17", "import {name};"),
+                ("Java_SE21", "import {name};"),
+                ("Kotlin", "import {name}"),
+                ("Scala", "import {name}"),
+            ]
+                .into_iter()
+                .map(|(ds, template)| (ds.to_string(), template.to_string()))
+                .collect(),
+            trim_prefixes: Default::default(),
+            window_keywords: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SearchConfig {
+    /// Maximum number of entries a single doc set contributes.
+    pub per_docset_limit: usize,
+    /// Entries a provider scores below this (0–100, 50 being a case-insensitive suffix match in
+    /// Dash) are dropped. Keyword completions are always shown.
+    pub min_relevance: usize,
+    /// Maximum number of entries a single provider contributes, across all its doc sets.
+    pub per_provider_limit: usize,
+    /// Merge results round-robin across doc sets rather than purely by relevance, so one doc
+    /// set with many strong matches doesn't hide the others. The rounds go across doc sets
+    /// whatever `group_by` shows.
+    pub interleave: bool,
+    /// Maximum number of entries returned to KRunner once every doc set is merged.
+    pub global_limit: usize,
+    /// Match names case-exactly. A single query can opt in with a `==` prefix, e.g. `rust ==String`.
+    pub case_sensitive: bool,
+    /// `Match` calls arriving within this many milliseconds of each other only run the newest
+    /// query, 0 runs every query.
+    pub debounce_ms: u64,
+    /// Maximum number of keyword completions listed while only a keyword is typed.
+    pub completion_limit: usize,
+    /// Shortest keyword KRunner sends us.
+    pub min_letters: usize,
+    /// How long a query waits for slow doc sets before returning what has arrived.
+    pub timeout_ms: u64,
+    /// `min_letters` and `global_limit` for explicit single runner invocations.
+    pub single_runner_min_letters: usize,
+    pub single_runner_global_limit: usize,
+    /// Doc sets searched at the same time, shared by every query in flight so overlapping
+    /// keystrokes can't stack up more database work.
+    pub max_concurrent_searches: usize,
+    /// Starting a query with this searches every doc set without a keyword, e.g. `??into_iter`.
+    /// Empty disables global search.
+    pub global_prefix: String,
+    /// Maximum number of entries returned by a global search, kept low since every doc set
+    /// contributes.
+    pub global_search_limit: usize,
+    /// Starting a query with this searches the doc set `Config::window_keywords` maps the
+    /// focused application to, e.g. `@@vec` from Kate. Follows focus through a KWin script,
+    /// queries from unmapped applications are searched as typed. Empty disables it.
+    pub focused_prefix: String,
+    /// Stop waiting for slower providers once this many perfect matches (the exact name in a
+    /// doc set named exactly by the keyword) are in, capped at the query's limit. 0 always
+    /// waits for every provider or the timeout.
+    pub early_return_matches: usize,
+    /// End capped results with a "Show more results" entry. Selecting it makes the next run of
+    /// the same query show the following page, the per doc set and per provider limits growing
+    /// by the entries of the pages before it.
+    pub paginate: bool,
+    /// Keyword searched when the first word of a query is no doc set's keyword, e.g. `rust` to
+    /// find `Vec` by typing just that. Empty requires a keyword.
+    pub default_keyword: String,
+    /// Queries longer than this many characters, usually pasted by accident, get no results
+    /// rather than a slow search. 0 allows any length.
+    pub max_query_len: usize,
+    /// Lists the keyword completions of doc sets whose entries get opened often and lately
+    /// first. Opens are counted in a file under the state dir.
+    pub sort_completions_by_usage: bool,
+    /// Match names whatever their accents, `cafe` finding `café` and the other way around.
+    /// Dash doc sets then rank a wider set of candidates in memory, which costs some speed.
+    pub fold_diacritics: bool,
+    /// Entry types never shown, e.g. `["Guide", "Sample"]`. Starting the search term with `*`
+    /// shows them for that query.
+    pub hidden_types: Vec<EntryType>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            per_docset_limit: 30,
+            min_relevance: 0,
+            per_provider_limit: 50,
+            interleave: false,
+            global_limit: 50,
+            case_sensitive: false,
+            debounce_ms: 0,
+            completion_limit: 10,
+            min_letters: 1,
+            timeout_ms: 150,
+            single_runner_min_letters: 0,
+            single_runner_global_limit: 100,
+            max_concurrent_searches: 8,
+            global_prefix: "??".into(),
+            global_search_limit: 20,
+            focused_prefix: String::new(),
+            early_return_matches: 50,
+            paginate: false,
+            default_keyword: String::new(),
+            max_query_len: 128,
+            sort_completions_by_usage: false,
+            fold_diacritics: false,
+            hidden_types: vec![],
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Where doc set usage is counted, `None` when completions aren't sorted by it or there's
+    /// no state dir.
+    pub fn usage_file(&self) -> Option<PathBuf> {
+        match self.sort_completions_by_usage {
+            true => dirs::state_dir().map(|d| d.join("kdashdoc").join("usage.json")),
+            false => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DashConfig {
+    /// Folder holding the `*.docset` folders, Zeal's docset folder when unset.
+    pub root: Option<PathBuf>,
+    /// Remember doc set metadata between runs so startup doesn't parse every meta.json.
+    pub cache_metadata: bool,
+    /// Let a doc set's name trigger it on top of its declared keywords.
+    pub name_as_keyword: bool,
+    /// Settings of single doc sets, by doc set name.
+    pub doc_sets: HashMap<String, DocSetConfig>,
+    pub open_mode: OpenMode,
+    /// Match queries against the bare symbol of entry names that are full declarations, e.g.
+    /// `push` for `fn push(&mut self, value: T)`. Names are still shown whole.
+    pub normalize_signatures: bool,
+    /// Also match the query against entry paths, ranked below every name match. Finds pages
+    /// whose file name says more than their entry name.
+    pub search_paths: bool,
+}
+
+/// Where Dash entries are opened.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenMode {
+    /// The entry's HTML page in the default browser.
+    #[default]
+    Browser,
+    /// Zeal, searching the entry through its `dash-plugin://` URL scheme. Falls back to the
+    /// browser when nothing handles that scheme.
+    Zeal,
+    /// The entry's page in the default browser, served from a small HTTP server on localhost
+    /// so stylesheets and scripts the page refers to load like they would online.
+    Server,
+}
+
+impl Default for DashConfig {
+    fn default() -> Self {
+        Self {
+            root: None,
+            cache_metadata: true,
+            name_as_keyword: true,
+            doc_sets: Default::default(),
+            open_mode: Default::default(),
+            normalize_signatures: false,
+            search_paths: false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct DocSetConfig {
+    /// Overrides `DashConfig::name_as_keyword` for this doc set.
+    pub name_as_keyword: Option<bool>,
+    /// Overrides `DashConfig::normalize_signatures` for this doc set.
+    pub normalize_signatures: Option<bool>,
+    /// Most entries a search of this doc set returns, in place of the `resultLimit` its
+    /// meta.json may declare. Only ever lowers `SearchConfig::per_docset_limit`.
+    pub limit: Option<usize>,
+}
+
+impl DashConfig {
+    pub fn docset_root(&self) -> anyhow::Result<PathBuf> {
+        match &self.root {
+            Some(root) => Ok(root.clone()),
+            None => Ok(dirs::data_dir()
+                .context("Unable to find data dir")?
+                .join("Zeal")
+                .join("Zeal")
+                .join("docsets")),
+        }
+    }
+
+    pub fn name_as_keyword(&self, doc_set: &str) -> bool {
+        self.doc_sets
+            .get(doc_set)
+            .and_then(|ds| ds.name_as_keyword)
+            .unwrap_or(self.name_as_keyword)
+    }
+
+    /// The result limit of a doc set, configured or else `declared` by the doc set itself.
+    pub fn result_limit(&self, doc_set: &str, declared: Option<usize>) -> Option<usize> {
+        self.doc_sets
+            .get(doc_set)
+            .and_then(|ds| ds.limit)
+            .or(declared)
+    }
+
+    pub fn normalize_signatures(&self, doc_set: &str) -> bool {
+        self.doc_sets
+            .get(doc_set)
+            .and_then(|ds| ds.normalize_signatures)
+            .unwrap_or(self.normalize_signatures)
+    }
+
+    /// Where doc set metadata is cached, `None` when caching is off or there's no cache dir.
+    pub fn metadata_cache_file(&self) -> Option<PathBuf> {
+        match self.cache_metadata {
+            true => dirs::cache_dir().map(|d| d.join("kdashdoc").join("dash-metadata.json")),
+            false => None,
+        }
+    }
+}
+
+/// Installing doc sets from Kapeli's feeds reaches out to the network, so it's opt-in.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct FeedsConfig {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct InfoConfig {
+    /// Folders holding a GNU info `dir` file.
+    pub dirs: Vec<PathBuf>,
+    /// Terminal command the `info` viewer is launched in, e.g. `["konsole", "-e"]`.
+    pub terminal: Vec<String>,
+}
+
+impl Default for InfoConfig {
+    fn default() -> Self {
+        Self {
+            dirs: vec!["/usr/share/info".into(), "/usr/local/share/info".into()],
+            terminal: vec!["konsole".into(), "-e".into()],
+        }
+    }
+}
+
+/// Where to find a local copy of the CPython HTML docs.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PyDocConfig {
+    /// Candidate docs folders, the first one holding an `objects.inv` is used.
+    pub dirs: Vec<PathBuf>,
+    pub keyword: String,
+}
+
+impl Default for PyDocConfig {
+    fn default() -> Self {
+        Self {
+            dirs: vec![
+                "/usr/share/doc/python3/html".into(),
+                "/usr/share/doc/python/html".into(),
+                "/usr/local/share/doc/python3/html".into(),
+            ],
+            keyword: "pydoc".into(),
+        }
+    }
+}
+
+/// Go packages documented through `go doc`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GoDocConfig {
+    /// Import paths indexed on start, e.g. `net/http`.
+    pub packages: Vec<String>,
+    pub keyword: String,
+    /// The `go` binary to run.
+    pub go: PathBuf,
+    /// How long `go doc` may take for a single package.
+    pub timeout_ms: u64,
+    /// Where entries are opened, `{base}/{package}#{symbol}`.
+    pub base_url: String,
+}
+
+impl Default for GoDocConfig {
+    fn default() -> Self {
+        Self {
+            packages: ["bytes", "context", "errors", "fmt", "io", "net/http", "os", "sort", "strings", "sync", "time"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            keyword: "go".into(),
+            go: "go".into(),
+            timeout_ms: 5000,
+            base_url: "https://pkg.go.dev".into(),
+        }
+    }
+}
+
+/// Folders of markdown files to serve.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct MarkdownConfig {
+    pub roots: Vec<MarkdownRootConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MarkdownRootConfig {
+    pub path: PathBuf,
+    /// Display name, the folder name when unset.
+    pub name: Option<String>,
+    /// Keywords triggering this root, the lowercased name when empty.
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+/// A JSON search endpoint on localhost, `GET /search?q=rust+vec`, for editors and launchers
+/// that can't use D-Bus. Off unless enabled.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7357,
+        }
+    }
+}
+
+/// Online lookups on devdocs.io, used once `DevDocs` is added to the enabled providers.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DevDocsConfig {
+    /// DevDocs doc slugs by keyword, e.g. `rs = "rust"`.
+    pub docs: HashMap<String, String>,
+    /// How long a single request to DevDocs may take.
+    pub timeout_ms: u64,
+}
+
+impl Default for DevDocsConfig {
+    fn default() -> Self {
+        Self {
+            docs: Default::default(),
+            timeout_ms: 5000,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("kdashdoc").join("config.toml"))
+    }
+
+    pub async fn load() -> anyhow::Result<Self> {
+        let path = match Self::path() {
+            Some(v) => v,
+            None => return Ok(Default::default()),
+        };
+
+        match tokio::fs::read_to_string(&path).await {
+            Ok(s) => toml::from_str(&s).with_context(|| format!("Parsing {}", path.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                log::debug!("No config at {}, using defaults", path.display());
+                Ok(Default::default())
+            }
+            Err(e) => Err(e).with_context(|| format!("Reading {}", path.display())),
+        }
+    }
+}
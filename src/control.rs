@@ -0,0 +1,333 @@
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+use zbus::{dbus_interface, fdo::Error, fdo::Result};
+
+use crate::config::Config;
+use crate::error::DocError;
+use crate::feeds::Feeds;
+use crate::provider::{DocSetCheck, EntryType, ProviderStatus, SearchOptions};
+use crate::registry::{build_providers, ProviderSet};
+
+/// Troubleshooting and management methods served next to the KRunner interface.
+pub struct Control {
+    providers: ProviderSet,
+    feeds: Option<Feeds>,
+    /// The config last loaded, replaced by `Reload`.
+    config: RwLock<Arc<Config>>,
+}
+
+impl Control {
+    pub fn new(providers: ProviderSet, feeds: Option<Feeds>, config: Arc<Config>) -> Self {
+        Self { providers, feeds, config: RwLock::new(config) }
+    }
+
+    fn config(&self) -> Arc<Config> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Switches the providers over to what `config` enables, see `Reload`.
+    async fn apply(&self, config: Config) -> anyhow::Result<()> {
+        let current = self.providers.snapshot();
+        let providers = build_providers(&config, &current).await?;
+        // Freshly built providers have just loaded everything
+        for p in providers.iter().filter(|p| current.iter().any(|c| c.name() == p.name())) {
+            if let Err(e) = p.reload().await {
+                log::error!("Error reloading provider {}: {e:?}", p.name());
+            }
+        }
+        log::info!("Reloaded providers: {:?}", providers.iter().map(|p| p.name()).collect::<Vec<_>>());
+        self.providers.replace(providers.clone())?;
+        *self.config.write().unwrap() = Arc::new(config);
+
+        // Disabled providers let go of their servers and watchers once nothing can reach them
+        for p in current.iter().filter(|c| !providers.iter().any(|p| p.name() == c.name())) {
+            if let Err(e) = p.clean_up().await {
+                log::error!("Error cleaning up provider {}: {e:?}", p.name());
+            }
+        }
+        Ok(())
+    }
+
+    fn feeds(&self) -> Result<&Feeds> {
+        self.feeds
+            .as_ref()
+            .ok_or_else(|| Error::NotSupported("Doc set feeds are disabled in the config".into()))
+    }
+}
+
+/// A doc set entry as returned by `SearchDocSet`.
+#[derive(Debug, Serialize)]
+struct SearchResult<'a> {
+    entry_type: &'a EntryType,
+    title: &'a str,
+    desc: &'a str,
+    id: &'a str,
+    url: Option<String>,
+    relevance: usize,
+}
+
+/// The doc set checks of a provider as returned by `CheckDocSets`.
+#[derive(Debug, Serialize)]
+struct ProviderCheck {
+    provider: String,
+    doc_sets: Vec<DocSetCheck>,
+    error: Option<String>,
+}
+
+#[dbus_interface(name = "dev.fanchao.DashDoc.Control")]
+impl Control {
+    /// Returns a JSON summary of what every provider has loaded.
+    async fn status(&self) -> Result<String> {
+        let mut rs = vec![];
+        for p in self.providers.snapshot().iter() {
+            rs.push(p.status().await.unwrap_or_else(|e| ProviderStatus {
+                provider: p.name().to_string(),
+                error: Some(format!("{e:?}")),
+                ..Default::default()
+            }));
+        }
+
+        serde_json::to_string_pretty(&rs).map_err(|e| Error::Failed(e.to_string()))
+    }
+
+    /// Checks the integrity of every loaded doc set, returning a JSON report per provider with
+    /// the checks each doc set passed or failed.
+    async fn check_doc_sets(&self) -> Result<String> {
+        let mut rs = vec![];
+        for p in self.providers.snapshot().iter() {
+            let (doc_sets, error) = match p.check().await {
+                Ok(v) => (v, None),
+                Err(e) => (vec![], Some(format!("{e:#}"))),
+            };
+            rs.push(ProviderCheck { provider: p.name().to_string(), doc_sets, error });
+        }
+
+        serde_json::to_string_pretty(&rs).map_err(|e| Error::Failed(e.to_string()))
+    }
+
+    /// Re-reads the config and enables/disables providers to match its provider list.
+    /// Providers that stay enabled refresh what they've loaded, other settings apply on restart.
+    async fn reload(&self) -> Result<()> {
+        let config = Config::load().await.map_err(DocError::from)?;
+        self.apply(config).await.map_err(DocError::from)?;
+        Ok(())
+    }
+
+    /// Returns the configuration the service is running with as JSON, defaults included and the
+    /// doc set root resolved.
+    async fn dump_config(&self) -> Result<String> {
+        let mut config = self.config().as_ref().clone();
+        if config.dash.root.is_none() {
+            config.dash.root = config.dash.docset_root().ok();
+        }
+        serde_json::to_string_pretty(&config).map_err(|e| Error::Failed(e.to_string()))
+    }
+
+    /// Searches the doc set `doc_set_id` of `provider` for `query` without going through keywords,
+    /// returning a JSON list of entries along with the URL each one opens.
+    async fn search_doc_set(&self, provider: &str, doc_set_id: &str, query: &str) -> Result<String> {
+        let p = self.providers.require(provider)?;
+        let doc_sets = p.doc_sets().await.map_err(DocError::from)?;
+        if !doc_sets.iter().any(|ds| ds.id.as_ref() == doc_set_id) {
+            return Err(DocError::DocSetNotFound {
+                provider: provider.to_string(),
+                doc_set: doc_set_id.to_string(),
+            }.into());
+        }
+
+        let config = self.config();
+        let options = SearchOptions {
+            limit: config.search.per_docset_limit,
+            case_sensitive: config.search.case_sensitive,
+            fold_diacritics: config.search.fold_diacritics,
+            hidden_types: config.search.hidden_types.clone(),
+            global: false,
+        };
+        let entries = p.search(doc_set_id, query, &options)
+            .await
+            .map_err(DocError::from)?;
+        let mut rs = Vec::with_capacity(entries.len());
+        for e in &entries {
+            rs.push(SearchResult {
+                entry_type: &e.entry_type,
+                title: &e.title,
+                desc: &e.desc,
+                id: &e.id,
+                url: p.resolve_url(doc_set_id, &e.id).await,
+                relevance: e.relevance,
+            });
+        }
+        serde_json::to_string_pretty(&rs).map_err(|e| Error::Failed(e.to_string()))
+    }
+
+    /// Returns a JSON list of the doc sets that can be installed.
+    async fn list_available(&self) -> Result<String> {
+        let available = self.feeds()?
+            .list_available()
+            .await
+            .map_err(DocError::from)?;
+        serde_json::to_string_pretty(&available).map_err(|e| Error::Failed(e.to_string()))
+    }
+
+    /// Installs the doc set `name` into the doc set root, returning the installed folder.
+    /// The Dash provider rescans its doc sets right after, so the new one is searchable at once.
+    async fn install(&self, name: &str) -> Result<String> {
+        let path = self.feeds()?
+            .install(name)
+            .await
+            .map_err(DocError::from)?;
+        log::info!("Installed doc set {name} into {}", path.display());
+        if let Some(dash) = self.providers.find("Dash") {
+            if let Err(e) = dash.reload().await {
+                log::error!("Error reloading Dash doc sets after installing {name}: {e:?}");
+            }
+        }
+        Ok(path.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use async_trait::async_trait;
+
+    use crate::provider::{DocProvider, DocSet, DynProvider, SearchEntry};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MockProvider {
+        name: &'static str,
+        /// The one doc set served, holding a single `Vec` entry.
+        doc_set: Option<&'static str>,
+        cleaned_up: AtomicBool,
+        reloaded: AtomicBool,
+    }
+
+    #[async_trait]
+    impl DocProvider for MockProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>> {
+            Ok(self.doc_set
+                .into_iter()
+                .map(|id| DocSet {
+                    id: id.into(),
+                    keywords: vec![id.into()],
+                    name: id.into(),
+                    description: Arc::from(""),
+                    icon: Arc::from(""),
+                    homepage: None,
+                })
+                .collect())
+        }
+
+        async fn search_doc_sets(&self, _keyword: &str) -> anyhow::Result<Vec<DocSet>> {
+            Ok(vec![])
+        }
+
+        async fn search(&self, _doc_set_id: &str, q: &str, options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>> {
+            let entry = SearchEntry {
+                entry_type: EntryType::Struct,
+                title: Arc::from("Vec"),
+                desc: Arc::from("std::vec"),
+                id: Arc::from("vec.html"),
+                relevance: 100,
+            };
+            Ok(Some(entry)
+                .filter(|e| e.title.to_lowercase().contains(&q.to_lowercase()))
+                .into_iter()
+                .take(options.limit)
+                .collect())
+        }
+
+        async fn open(&self, _doc_set_id: &str, _entry_url: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn resolve_url(&self, doc_set_id: &str, entry_url: &str) -> Option<String> {
+            Some(format!("https://docs.example/{doc_set_id}/{entry_url}"))
+        }
+
+        async fn reload(&self) -> anyhow::Result<()> {
+            self.reloaded.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn clean_up(&self) -> anyhow::Result<()> {
+            self.cleaned_up.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn reload_drops_disabled_providers_and_keeps_the_new_config() {
+        let dash = Arc::new(MockProvider { name: "Dash", ..Default::default() });
+        let tldr = Arc::new(MockProvider { name: "tldr", ..Default::default() });
+        let providers = ProviderSet::new(vec![dash.clone() as DynProvider, tldr.clone() as DynProvider]).unwrap();
+        let config = Config { providers: vec!["Dash".into(), "tldr".into()], ..Config::default() };
+        let control = Control::new(providers.clone(), None, Arc::new(config));
+
+        let mut config = Config { providers: vec!["Dash".into()], ..Config::default() };
+        config.search.per_docset_limit = 7;
+        control.apply(config).await.unwrap();
+
+        let names: Vec<String> = providers.snapshot().iter().map(|p| p.name().to_string()).collect();
+        assert_eq!(names, vec!["Dash"]);
+        assert!(tldr.cleaned_up.load(Ordering::SeqCst));
+        assert!(!dash.cleaned_up.load(Ordering::SeqCst));
+        let dumped: serde_json::Value = serde_json::from_str(&control.dump_config().await.unwrap()).unwrap();
+        assert_eq!(dumped["providers"], serde_json::json!(["Dash"]));
+        assert_eq!(dumped["search"]["per_docset_limit"], 7);
+    }
+
+    #[tokio::test]
+    async fn reload_refreshes_the_providers_that_stay() {
+        let dash = Arc::new(MockProvider { name: "Dash", ..Default::default() });
+        let tldr = Arc::new(MockProvider { name: "tldr", ..Default::default() });
+        let providers = ProviderSet::new(vec![dash.clone() as DynProvider, tldr.clone() as DynProvider]).unwrap();
+        let control = Control::new(providers, None, Arc::new(Config::default()));
+
+        control.apply(Config { providers: vec!["Dash".into()], ..Config::default() }).await.unwrap();
+        assert!(dash.reloaded.load(Ordering::SeqCst));
+        assert!(!tldr.reloaded.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn searches_a_doc_set_by_id() {
+        let dash = Arc::new(MockProvider { name: "Dash", doc_set: Some("rust"), ..Default::default() });
+        let providers = ProviderSet::new(vec![dash as DynProvider]).unwrap();
+        let control = Control::new(providers, None, Arc::new(Config::default()));
+
+        let rs: serde_json::Value = serde_json::from_str(&control.search_doc_set("Dash", "rust", "vec").await.unwrap()).unwrap();
+        assert_eq!(rs, serde_json::json!([{
+            "entry_type": "Struct",
+            "title": "Vec",
+            "desc": "std::vec",
+            "id": "vec.html",
+            "url": "https://docs.example/rust/vec.html",
+            "relevance": 100,
+        }]));
+        assert!(matches!(control.search_doc_set("tldr", "rust", "vec").await, Err(Error::InvalidArgs(_))));
+        assert!(matches!(control.search_doc_set("Dash", "python", "vec").await, Err(Error::InvalidArgs(_))));
+    }
+
+    #[tokio::test]
+    async fn dumps_the_defaults_with_the_doc_set_root_resolved() {
+        let providers = ProviderSet::new(vec![]).unwrap();
+        let control = Control::new(providers, None, Arc::new(Config::default()));
+
+        let dumped: serde_json::Value = serde_json::from_str(&control.dump_config().await.unwrap()).unwrap();
+        assert_eq!(dumped["providers"], serde_json::json!(["Dash"]));
+        assert_eq!(dumped["idle_timeout_minutes"], 0);
+        assert_eq!(dumped["search"]["min_letters"], 1);
+        assert_eq!(dumped["search"]["per_docset_limit"], 30);
+        assert_eq!(dumped["search"]["global_limit"], 50);
+        let root = Config::default().dash.docset_root().unwrap();
+        assert_eq!(dumped["dash"]["root"], serde_json::json!(root));
+    }
+}
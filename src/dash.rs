@@ -1,193 +1,1943 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
 
 use anyhow::Context;
 use async_trait::async_trait;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use sqlx::{Row, SqlitePool};
-use sqlx::sqlite::{SqliteConnectOptions, SqliteRow};
+use sqlx::{Row, Sqlite, SqlitePool};
+use sqlx::query::Query;
+use sqlx::sqlite::{SqliteArguments, SqliteConnectOptions, SqlitePoolOptions, SqliteRow};
 use tokio::fs::{read_dir};
 use tokio::task::spawn_blocking;
+use tokio::time::sleep;
+use unicode_normalization::UnicodeNormalization;
 
-use crate::provider::{DocProvider, DocSet, SearchEntry};
+use crate::config::{DashConfig, OpenMode};
+use crate::docserver::DocServer;
+use crate::logging::debug_throttled;
+use crate::opener;
+use crate::scorers::{self, DocSetScorer};
+use crate::provider::{name_relevance, sort_by_keyword, strip_diacritics, Check, DocProvider, DocSet, DocSetCheck, DocSetStatus, ProviderStatus, SearchEntry, SearchOptions};
 
-const EXTRA_KEYWORDS: &[(&'static str, &'static str)] = &[
+const EXTRA_KEYWORDS: &[(&str, &str)] = &[
     ("Android", "droid"),
 ];
 
 pub struct Dash {
+    config: DashConfig,
+    open_mode: OpenMode,
+    search_paths: bool,
+    loaded: RwLock<Arc<Loaded>>,
+    /// Serves pages in [OpenMode::Server], started with the first page opened.
+    server: DocServer,
+}
+
+/// The doc sets found under the root, replaced as a whole on [DocProvider::reload] so searches
+/// already running finish against the set they started with.
+struct Loaded {
+    root: PathBuf,
     doc_sets: Vec<DashDocSet>,
+    keyword_index: KeywordIndex,
+    skipped: usize,
 }
 
 impl Dash {
-    pub async fn new_with_default() -> anyhow::Result<Self> {
-        let root = dirs::data_dir()
-            .context("Unable to find data dir")?
-            .join("Zeal")
-            .join("Zeal")
-            .join("docsets");
-
-        let mut entries = read_dir(&root).await.context("Listing docset folder")?;
+    pub async fn new(config: &DashConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            config: config.clone(),
+            open_mode: config.open_mode,
+            search_paths: config.search_paths,
+            loaded: RwLock::new(Arc::new(Loaded::load(config).await?)),
+            server: Default::default(),
+        })
+    }
+
+    fn loaded(&self) -> Arc<Loaded> {
+        self.loaded.read().unwrap().clone()
+    }
+}
+
+impl Loaded {
+    /// Loads the doc sets under the configured root. With the metadata cache on, the metadata
+    /// of unchanged doc sets is taken from there instead of their meta.json.
+    async fn load(config: &DashConfig) -> anyhow::Result<Self> {
+        let root = config.docset_root()?;
+        let mut cache = match config.metadata_cache_file() {
+            Some(file) => MetaCache::load(file).await,
+            None => MetaCache::default(),
+        };
+        let mut entries = match read_dir(&root).await {
+            Ok(v) => v,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                log::warn!("Doc set folder {} doesn't exist, no Dash doc sets loaded", root.display());
+                return Ok(Self {
+                    root,
+                    doc_sets: vec![],
+                    keyword_index: Default::default(),
+                    skipped: 0,
+                });
+            }
+            Err(e) => return Err(e).with_context(|| format!("Listing docset folder {}", root.display())),
+        };
         let mut doc_sets = vec![];
+        let mut skipped = 0;
         while let Some(entry) = entries.next_entry().await? {
-            let set = match DashDocSet::new(entry.path()).await {
+            let set = match DashDocSet::new(&entry.path(), &mut cache, config).await {
                 Ok(v) => v,
+                Err(LoadError::NotADocSet) => {
+                    log::debug!("Ignoring {}: not a docset", entry.path().display());
+                    continue;
+                }
+                Err(e @ LoadError::Database(_)) => {
+                    log::error!("Skipping docset {}: {e}", entry.path().display());
+                    skipped += 1;
+                    continue;
+                }
                 Err(e) => {
-                    log::error!("Ignoring docset folder {}: {e:?}", entry.path().display());
+                    log::warn!("Skipping docset {}: {e}", entry.path().display());
+                    skipped += 1;
                     continue;
                 }
             };
             doc_sets.push(set);
         }
         log::debug!("Parsed doc sets: {doc_sets:#?}");
-        Ok(Self { doc_sets })
+        if let Err(e) = cache.save().await {
+            log::warn!("Unable to save doc set metadata cache: {e:?}");
+        }
+        let keyword_index = KeywordIndex::new(&doc_sets);
+        Ok(Self {
+            root,
+            doc_sets,
+            keyword_index,
+            skipped,
+        })
+    }
+
+    fn doc_set(&self, id: &str) -> Option<&DashDocSet> {
+        self.doc_sets.iter().find(|ds| ds.id.as_ref().eq(id))
+    }
+}
+
+/// Every doc set keyword sorted, so a prefix lookup is a binary search instead of a full scan.
+///
+/// Keywords are folded with the Unicode aware `to_lowercase`, callers must fold the typed
+/// keyword the same way. Prefix checks stay byte based, which is sound since both sides went
+/// through the same folding.
+#[derive(Debug, Default)]
+struct KeywordIndex {
+    entries: Vec<(Arc<str>, usize)>,
+}
+
+impl KeywordIndex {
+    fn new(doc_sets: &[DashDocSet]) -> Self {
+        Self::from_keywords(doc_sets.iter().map(|ds| ds.keywords.as_slice()))
+    }
+
+    /// Indexes the keywords of each doc set, in doc set order.
+    fn from_keywords<'a>(keywords: impl Iterator<Item = &'a [Arc<str>]>) -> Self {
+        let mut entries: Vec<(Arc<str>, usize)> = keywords
+            .enumerate()
+            .flat_map(|(i, keywords)| keywords.iter().map(move |k| (k.clone(), i)))
+            .collect();
+        entries.sort();
+        entries.dedup();
+        Self { entries }
+    }
+
+    /// Whether any doc set has a keyword that starts with `kw_lc`, see [KeywordIndex::lookup].
+    fn contains(&self, kw_lc: &str) -> bool {
+        let start = self.entries.partition_point(|(k, _)| k.as_ref() < kw_lc);
+        !kw_lc.is_empty() && self.entries.get(start).is_some_and(|(k, _)| k.starts_with(kw_lc))
+    }
+
+    /// Returns the indices of doc sets having a keyword that starts with `kw_lc`, in doc set order.
+    ///
+    /// Matching is one way only: the typed keyword must be a prefix of the doc set keyword, so
+    /// `py` finds `python` while `pythonista` does not. An empty keyword matches nothing.
+    fn lookup(&self, kw_lc: &str) -> Vec<usize> {
+        if kw_lc.is_empty() {
+            return vec![];
+        }
+
+        let start = self.entries.partition_point(|(k, _)| k.as_ref() < kw_lc);
+        let mut rs: Vec<usize> = self.entries[start..]
+            .iter()
+            .take_while(|(k, _)| k.starts_with(kw_lc))
+            .map(|(_, i)| *i)
+            .collect();
+        rs.sort_unstable();
+        rs.dedup();
+        rs
+    }
+}
+
+/// Why a folder under the doc set root didn't load.
+#[derive(Debug)]
+enum LoadError {
+    /// There's neither meta.json nor Info.plist, the folder most likely isn't a doc set at all.
+    NotADocSet,
+    Metadata(anyhow::Error),
+    MissingIndex(PathBuf),
+    /// The index exists but can't be opened: corrupt, locked or unreadable.
+    Database(anyhow::Error),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::NotADocSet => write!(f, "no meta.json or Info.plist"),
+            LoadError::Metadata(e) => write!(f, "invalid metadata: {e:?}"),
+            LoadError::MissingIndex(path) => write!(f, "missing index {}", path.display()),
+            LoadError::Database(e) => write!(f, "unable to open index: {e:?}"),
+        }
     }
 }
 
 #[derive(Debug)]
 struct DashDocSet {
+    /// The doc set's folder name, unique under the root unlike `name`.
+    id: Arc<str>,
     name: Arc<str>,
     title: Arc<str>,
     db: SqlitePool,
     icon: Option<Arc<str>>,
+    homepage: Option<Arc<str>>,
     keywords: Vec<Arc<str>>,
-    _resource_root: PathBuf,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-struct EntryId<'a> {
-    name: &'a str,
-    path: &'a str,
-    fragment: Option<&'a str>,
+    resource_root: PathBuf,
+    /// Landing page declared by the doc set, relative to `resource_root`.
+    index_file: Option<PathBuf>,
+    statements: Mutex<HashMap<SearchQuery, Statements>>,
+    schema: IndexSchema,
+    /// Whether the index has an `aliases` table, see [SearchQuery::alias_sql].
+    has_aliases: bool,
+    /// Entry names are declarations, matched by their bare symbol, see [signature_symbol].
+    normalize_signatures: bool,
+    /// Lowers the limit searches are run with, for doc sets whose long tail is noise.
+    result_limit: Option<usize>,
+    /// Re-ranks what the relevance SQL found.
+    scorer: &'static dyn DocSetScorer,
 }
 
 impl DashDocSet {
-    async fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let meta_path = path.as_ref().join("meta.json");
-        let meta = spawn_blocking(move || -> anyhow::Result<Value> {
-            Ok(serde_json::from_reader(std::fs::File::open(meta_path).context("Opening meta.json")?)?)
-        }).await??;
-
-        let res_dir = path.as_ref().join("Contents").join("Resources");
-        let db = SqlitePool::connect_with(SqliteConnectOptions::default().filename(res_dir.join("docSet.dsidx")).read_only(true)).await.context("Opening database")?;
-
-        let name: Arc<str> = meta.get("name").context("Reading name")?.as_str().context("name is not string")?.into();
-        let title = meta.get("name").context("Reading title")?.as_str().context("title is not string")?.into();
-        let keywords = meta.get("extra").and_then(|extra| extra.get("keywords")).and_then(|keywords| keywords.as_array())
-            .iter()
-            .flat_map(|v| v.iter())
-            .filter_map(|v| v.as_str())
-            .map(|s| s.to_ascii_lowercase().into())
-            .chain(vec![name.to_ascii_lowercase().into()].into_iter())
-            .chain(EXTRA_KEYWORDS.iter().filter(|item| item.0.eq(name.as_ref())).map(|item| item.1.into()))
-            .collect();
+    async fn new(path: &Path, cache: &mut MetaCache, config: &DashConfig) -> Result<Self, LoadError> {
+        let id: Arc<str> = path.file_name().map_or_else(|| path.to_string_lossy(), |n| n.to_string_lossy()).into();
+        // Symlinked doc set folders are followed, everything after works on the real location
+        let path = &tokio::fs::canonicalize(path)
+            .await
+            .with_context(|| format!("Resolving {}", path.display()))
+            .map_err(LoadError::Metadata)?;
+        // Zeal writes meta.json, doc sets straight from Dash only have their Info.plist
+        let meta_path = path.join("meta.json");
+        let plist_path = path.join("Contents").join("Info.plist");
+        // Editing the metadata file in place leaves the folder's time alone, so both count
+        let modified = modified_time(path).await;
+        let meta_modified = match modified_time(&meta_path).await {
+            Some(v) => Some(v),
+            None => modified_time(&plist_path).await,
+        };
+        if let Some(meta) = modified.and_then(|modified| cache.get(path, modified, meta_modified)) {
+            return Self::open(path, id, meta, config).await;
+        }
+
+        let meta = match (meta_path.is_file(), plist_path.is_file()) {
+            (true, _) => {
+                let meta = spawn_blocking(move || -> anyhow::Result<Value> {
+                    Ok(serde_json::from_reader(std::fs::File::open(meta_path).context("Opening meta.json")?)?)
+                }).await.map_err(|e| LoadError::Metadata(e.into()))?.map_err(LoadError::Metadata)?;
+                DocSetMeta::parse(path, meta)
+            }
+            (false, true) => {
+                let plist = spawn_blocking(move || plist::Value::from_file(plist_path).context("Reading Info.plist"))
+                    .await.map_err(|e| LoadError::Metadata(e.into()))?.map_err(LoadError::Metadata)?;
+                DocSetMeta::from_plist(path, plist)
+            }
+            (false, false) => return Err(LoadError::NotADocSet),
+        }.map_err(LoadError::Metadata)?;
+        if let Some(modified) = modified {
+            cache.insert(path, modified, meta_modified, meta.clone());
+        }
+        Self::open(path, id, meta, config).await
+    }
+
+    async fn open(path: &Path, id: Arc<str>, meta: DocSetMeta, config: &DashConfig) -> Result<Self, LoadError> {
+        let root = path.to_path_buf();
+        let layout = spawn_blocking(move || DocSetLayout::find(&root))
+            .await
+            .map_err(|e| LoadError::Metadata(e.into()))??;
+        log::debug!(
+            "Doc set {} uses index {} and documents in {}",
+            meta.name,
+            layout.index.display(),
+            layout.documents.display(),
+        );
+        let db = open_index_db(&layout.index).await.map_err(|e| LoadError::Database(e.into()))?;
+        let schema = IndexSchema::detect(&db).await.map_err(|e| LoadError::Database(e.into()))?;
+        let has_aliases = sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'aliases'")
+            .fetch_optional(&db)
+            .await
+            .map_err(|e| LoadError::Database(e.into()))?
+            .is_some();
+
+        let mut keywords = meta.keywords;
+        let name_keyword = meta.name.to_lowercase();
+        if config.name_as_keyword(&meta.name) && !keywords.contains(&name_keyword) {
+            keywords.push(name_keyword);
+        }
+        if keywords.is_empty() {
+            log::warn!("Doc set {} has no keywords and can't be searched", meta.name);
+        }
 
         Ok(Self {
-            name,
+            schema,
+            has_aliases,
+            normalize_signatures: config.normalize_signatures(&meta.name),
+            result_limit: config.result_limit(&meta.name, meta.result_limit),
+            scorer: scorers::scorer_for(&meta.name),
+            id,
+            name: meta.name.into(),
             db,
-            title,
-            keywords,
-            icon: Some(path.as_ref().join("icon@2x.png"))
-                .iter()
-                .filter(|p| p.is_file())
-                .filter_map(|p| p.to_str())
-                .map(|s| s.into())
-                .next(),
-            _resource_root: res_dir.join("Documents"),
+            title: meta.title.into(),
+            keywords: keywords.into_iter().map(Arc::from).collect(),
+            icon: meta.icon.map(Arc::from),
+            homepage: meta.homepage.map(Arc::from),
+            resource_root: layout.documents,
+            index_file: meta.index_file,
+            statements: Default::default(),
         })
     }
 
-    fn contains_keyword(&self, kw_lc: &str) -> bool {
-        self.keywords.iter().find(|k| k.starts_with(kw_lc)).is_some()
+    /// Returns the SQL for `query`, built once per doc set so its text stays identical between
+    /// calls and sqlx keeps reusing the prepared statement on each pooled connection.
+    fn statement(&self, query: &SearchQuery) -> Statements {
+        self.statements
+            .lock()
+            .unwrap()
+            .entry(query.clone())
+            .or_insert_with(|| Statements {
+                search: query.sql().into(),
+                aliases: self.has_aliases.then(|| query.alias_sql().into()),
+            })
+            .clone()
+    }
+
+    /// Turns an entry URL relative to the doc set (`path` or `path#fragment`) into a `file://` URL.
+    /// Entries linking out to the web (`http://` or `https://`) are returned as they are.
+    fn resolve_url(&self, entry_url: &str) -> anyhow::Result<String> {
+        if is_web_url(entry_url) {
+            return Ok(entry_url.to_string());
+        }
+
+        let url = EntryUrl::parse(entry_url);
+        let file = self.resolve_path(entry_url)?;
+        Ok(file_url(&file, url.query, url.fragment))
+    }
+
+    /// The URL `server` serves an entry at, see [OpenMode::Server].
+    async fn served_url(&self, server: &DocServer, entry_url: &str) -> anyhow::Result<String> {
+        let url = EntryUrl::parse(entry_url);
+        let file = self.resolve_path(entry_url)?;
+        let relative = file.strip_prefix(&self.resource_root)
+            .with_context(|| format!("{} is outside the documents of {}", file.display(), self.name))?;
+        let base = server.url(&self.id, &self.resource_root, relative).await?;
+        Ok(with_query_and_fragment(base, url.query, url.fragment))
+    }
+
+    /// Builds the `dash-plugin://` URL making Zeal search this doc set for the entry's name. Zeal
+    /// has no way to open a page directly, so the name is looked up from the entry's path.
+    async fn zeal_url(&self, entry_url: &str) -> anyhow::Result<String> {
+        let page = entry_url.split_once('#').map_or(entry_url, |(page, _)| page);
+        let name: String = sqlx::query_scalar(&format!(
+            "SELECT name FROM {} WHERE path = ?1 OR path = ?2 LIMIT 1",
+            self.schema.source()
+        ))
+            .bind(entry_url)
+            .bind(page)
+            .fetch_optional(&self.db)
+            .await?
+            .with_context(|| format!("No entry at {entry_url}"))?;
+        let keyword = self.keywords.first().with_context(|| format!("{} has no keyword", self.name))?;
+
+        Ok(format!(
+            "dash-plugin://keys={}&query={}",
+            utf8_percent_encode(keyword, NON_ALPHANUMERIC),
+            utf8_percent_encode(&name, NON_ALPHANUMERIC),
+        ))
+    }
+
+    /// The absolute path of the file an entry URL points to, without its query or fragment.
+    fn resolve_path(&self, entry_url: &str) -> anyhow::Result<PathBuf> {
+        anyhow::ensure!(!is_web_url(entry_url), "{entry_url} is an online page, not a file");
+        let file = self.resource_root.join(EntryUrl::parse(entry_url).path.as_ref());
+        anyhow::ensure!(file.is_file(), "{} doesn't exist", file.display());
+        Ok(file)
+    }
+
+    /// Finds the doc set's landing page: the declared index file, then `index.html`, then
+    /// the first HTML file found walking the documents breadth first.
+    async fn resolve_index(&self) -> anyhow::Result<PathBuf> {
+        let declared = self.index_file
+            .iter()
+            .map(|f| self.resource_root.join(EntryUrl::parse(&f.to_string_lossy()).path.as_ref()))
+            .chain([self.resource_root.join("index.html")])
+            .find(|p| p.is_file());
+        if let Some(index) = declared {
+            return Ok(index);
+        }
+
+        let root = self.resource_root.clone();
+        spawn_blocking(move || find_first_html(&root))
+            .await?
+            .with_context(|| format!("No HTML page in {}", self.resource_root.display()))
+    }
+
+    async fn check_health(&self) -> DocSetStatus {
+        let rs = sqlx::query(&format!("SELECT 1 FROM {} LIMIT 1", self.schema.source()))
+            .fetch_optional(&self.db)
+            .await;
+        DocSetStatus {
+            id: self.id.to_string(),
+            healthy: rs.is_ok(),
+            error: rs.err().map(|e| e.to_string()),
+        }
+    }
+
+    /// Opens the index, reads a row in the layout its schema was detected as, and looks for the
+    /// documents folder and the icon.
+    async fn check(&self) -> DocSetCheck {
+        let database = sqlx::query("SELECT 1 FROM sqlite_master LIMIT 1")
+            .fetch_optional(&self.db)
+            .await;
+        let schema = sqlx::query(&format!("SELECT name, type, path FROM {} LIMIT 1", self.schema.source()))
+            .fetch_optional(&self.db)
+            .await;
+        let documents = match self.resource_root.is_dir() {
+            true => None,
+            false => Some(format!("{} is not a folder", self.resource_root.display())),
+        };
+        let icon = match &self.icon {
+            Some(icon) if Path::new(icon.as_ref()).is_file() => None,
+            Some(icon) => Some(format!("{icon} is missing")),
+            None => Some("No icon.png or icon@2x.png".to_string()),
+        };
+
+        DocSetCheck::new(self.id.as_ref(), vec![
+            Check { name: "database", error: database.err().map(|e| e.to_string()) },
+            Check { name: "schema", error: schema.err().map(|e| e.to_string()) },
+            Check { name: "documents", error: documents },
+            Check { name: "icon", error: icon },
+        ])
     }
 
     fn to_doc_set(&self) -> DocSet {
         DocSet {
-            id: self.name.clone(),
-            keywords: self.keywords.iter().map(|v| v.clone()).collect(),
+            id: self.id.clone(),
+            keywords: self.keywords.to_vec(),
             name: self.name.clone(),
             description: self.title.clone(),
             icon: self.icon.clone().unwrap_or_else(|| Arc::from("")),
+            homepage: self.homepage.clone(),
+        }
+    }
+}
+
+/// What a doc set declares about itself, everything needed before opening its index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DocSetMeta {
+    name: String,
+    title: String,
+    /// Lowercased keywords the doc set declares, its name is added depending on the config.
+    keywords: Vec<String>,
+    icon: Option<String>,
+    /// Landing page, relative to the documents folder.
+    index_file: Option<PathBuf>,
+    /// Web page of the documented project, only read from meta.json.
+    #[serde(default)]
+    homepage: Option<String>,
+    /// Cap on the entries a search returns, `extra.resultLimit` in meta.json.
+    #[serde(default)]
+    result_limit: Option<usize>,
+}
+
+impl DocSetMeta {
+    fn parse(path: &Path, meta: Value) -> anyhow::Result<Self> {
+        let name = meta.get("name")
+            .and_then(|v| v.as_str())
+            .context("name is missing or not a string")?
+            .to_string();
+        let title = match meta.get("title") {
+            Some(title) => title.as_str().context("title is not a string")?.to_string(),
+            None => name.clone(),
+        };
+        let declared: Vec<String> = match meta.get("extra").and_then(|extra| extra.get("keywords")) {
+            None => vec![],
+            Some(Value::Array(items)) if items.iter().all(Value::is_string) => items
+                .iter()
+                .filter_map(|v| v.as_str())
+                .map(|s| s.to_lowercase())
+                .collect(),
+            Some(other) => {
+                log::warn!("Doc set {name} has malformed extra.keywords, expected an array of strings: {other}");
+                vec![]
+            }
+        };
+        let keywords = declared
+            .into_iter()
+            .chain(EXTRA_KEYWORDS.iter().filter(|item| item.0.eq(name.as_str())).map(|item| item.1.to_string()))
+            .collect();
+        let index_file = meta.get("index_file")
+            .or_else(|| meta.get("extra").and_then(|extra| extra.get("indexFilePath")))
+            .and_then(|v| v.as_str())
+            .map(PathBuf::from);
+        let homepage = ["homepage", "repository"]
+            .iter()
+            .find_map(|key| meta.get(*key).or_else(|| meta.get("extra").and_then(|extra| extra.get(*key))))
+            .and_then(|v| v.as_str())
+            .filter(|url| is_web_url(url))
+            .map(str::to_string);
+        let result_limit = match meta.get("extra").and_then(|extra| extra.get("resultLimit")) {
+            None => None,
+            Some(limit) => match limit.as_u64() {
+                Some(limit) if limit > 0 => Some(limit as usize),
+                _ => {
+                    log::warn!("Doc set {name} has malformed extra.resultLimit, expected a positive integer: {limit}");
+                    None
+                }
+            },
+        };
+
+        Ok(Self {
+            name,
+            title,
+            keywords,
+            icon: find_icon(path),
+            index_file,
+            homepage,
+            result_limit,
+        })
+    }
+
+    /// Reads the metadata of a doc set that only has an `Info.plist`. Its platform family is
+    /// the keyword Dash itself uses for it.
+    fn from_plist(path: &Path, plist: plist::Value) -> anyhow::Result<Self> {
+        let info = plist.into_dictionary().context("Info.plist is not a dictionary")?;
+        let string = |key: &str| info.get(key).and_then(plist::Value::as_string);
+
+        let name = string("CFBundleName").context("CFBundleName is missing or not a string")?.to_string();
+        let keywords = string("DocSetPlatformFamily")
+            .map(|family| family.to_lowercase())
+            .into_iter()
+            .collect();
+
+        Ok(Self {
+            title: name.clone(),
+            name,
+            keywords,
+            icon: find_icon(path),
+            index_file: string("dashIndexFilePath").map(PathBuf::from),
+            homepage: None,
+            result_limit: None,
+        })
+    }
+}
+
+async fn modified_time(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.and_then(|m| m.modified()).ok()
+}
+
+fn find_icon(path: &Path) -> Option<String> {
+    ["icon@2x.png", "icon.png"]
+        .iter()
+        .map(|f| path.join(f))
+        .find(|p| p.is_file())
+        .and_then(|p| p.to_str().map(|s| s.to_string()))
+}
+
+/// Doc set metadata kept under the cache dir between runs, so unchanged doc sets skip reading
+/// and parsing their meta.json or Info.plist. Entries are keyed by doc set folder and go stale as soon as
+/// the modification time of the folder or of its metadata file changes.
+#[derive(Default)]
+struct MetaCache {
+    file: Option<PathBuf>,
+    loaded: HashMap<String, CachedMeta>,
+    /// Entries of the doc sets seen in this run, what gets saved.
+    current: HashMap<String, CachedMeta>,
+    dirty: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedMeta {
+    modified: SystemTime,
+    /// Of the meta.json or Info.plist the metadata was read from, missing in older caches.
+    #[serde(default)]
+    meta_modified: Option<SystemTime>,
+    meta: DocSetMeta,
+}
+
+impl MetaCache {
+    async fn load(file: PathBuf) -> Self {
+        let loaded = match tokio::fs::read(&file).await {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                log::warn!("Ignoring unreadable metadata cache {}: {e:?}", file.display());
+                Default::default()
+            }),
+            Err(e) => {
+                log::debug!("No metadata cache at {}: {e:?}", file.display());
+                Default::default()
+            }
+        };
+        Self { file: Some(file), loaded, ..Default::default() }
+    }
+
+    fn get(&mut self, path: &Path, modified: SystemTime, meta_modified: Option<SystemTime>) -> Option<DocSetMeta> {
+        let key = path.to_string_lossy().to_string();
+        let cached = self.loaded
+            .remove(&key)
+            .filter(|c| c.modified == modified && c.meta_modified == meta_modified)?;
+        let meta = cached.meta.clone();
+        self.current.insert(key, cached);
+        Some(meta)
+    }
+
+    fn insert(&mut self, path: &Path, modified: SystemTime, meta_modified: Option<SystemTime>, meta: DocSetMeta) {
+        self.current.insert(path.to_string_lossy().to_string(), CachedMeta { modified, meta_modified, meta });
+        self.dirty = true;
+    }
+
+    /// Writes the entries seen in this run, dropping doc sets that are gone.
+    async fn save(self) -> anyhow::Result<()> {
+        let file = match self.file {
+            Some(file) if self.dirty || !self.loaded.is_empty() => file,
+            _ => return Ok(()),
+        };
+
+        if let Some(dir) = file.parent() {
+            tokio::fs::create_dir_all(dir).await.with_context(|| format!("Creating {}", dir.display()))?;
+        }
+        tokio::fs::write(&file, serde_json::to_vec(&self.current)?).await
+            .with_context(|| format!("Writing {}", file.display()))
+    }
+}
+
+/// Where a doc set keeps its index and its pages. Dash's own layout is
+/// `Contents/Resources/docSet.dsidx` next to `Contents/Resources/Documents`, but older and
+/// hand-made doc sets vary the case, the index name, or flatten the folders.
+struct DocSetLayout {
+    index: PathBuf,
+    documents: PathBuf,
+}
+
+impl DocSetLayout {
+    fn find(path: &Path) -> Result<Self, LoadError> {
+        let contents = find_child(path, "Contents").unwrap_or_else(|| path.to_path_buf());
+        let resources = find_child(&contents, "Resources").unwrap_or_else(|| contents.clone());
+        let index = find_child(&resources, "docSet.dsidx")
+            .filter(|p| p.is_file())
+            .or_else(|| find_by_extension(&resources, "dsidx"))
+            .ok_or_else(|| LoadError::MissingIndex(path.join("Contents").join("Resources").join("docSet.dsidx")))?;
+        let documents = find_child(&resources, "Documents")
+            .filter(|p| p.is_dir())
+            .unwrap_or(resources);
+        Ok(Self { index, documents })
+    }
+}
+
+/// The entry of `dir` named `name` in any case, preferring the exact spelling.
+fn find_child(dir: &Path, name: &str) -> Option<PathBuf> {
+    let exact = dir.join(name);
+    if exact.exists() {
+        return Some(exact);
+    }
+
+    let mut matches: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().eq_ignore_ascii_case(name))
+        .map(|e| e.path())
+        .collect();
+    matches.sort();
+    matches.into_iter().next()
+}
+
+/// The first file of `dir`, by name, with extension `ext` in any case.
+fn find_by_extension(dir: &Path, ext: &str) -> Option<PathBuf> {
+    let mut matches: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case(ext)))
+        .collect();
+    matches.sort();
+    matches.into_iter().next()
+}
+
+fn find_first_html(root: &Path) -> Option<PathBuf> {
+    let mut dirs = std::collections::VecDeque::from([root.to_path_buf()]);
+    while let Some(dir) = dirs.pop_front() {
+        let mut paths: Vec<PathBuf> = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries.flatten().map(|e| e.path()).collect(),
+            Err(_) => continue,
+        };
+        paths.sort();
+
+        for path in paths {
+            if path.is_dir() {
+                dirs.push_back(path);
+            } else if path.extension().is_some_and(|ext| ext == "html" || ext == "htm") {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+/// Describes a relevance-ranked search over a doc set's index, whichever [IndexSchema] it uses.
+///
+/// The query text is bound as `?1`. Folding diacritics binds a strict [diacritic_pattern] of the
+/// query instead, and a loose one as `?2`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SearchQuery {
+    schema: IndexSchema,
+    limit: usize,
+    case_sensitive: bool,
+    /// Whether entries matched only by their path are found too.
+    search_paths: bool,
+    /// Only filter names containing the query, ranking is left to [signature_symbol] matching.
+    signatures: bool,
+    /// Only filter names matching a [diacritic_pattern], ranking is left to matching the folded
+    /// names.
+    fold_diacritics: bool,
+    /// Entry types filtered out in SQL, so they don't use up the limit.
+    hidden_types: Vec<Arc<str>>,
+}
+
+/// The SQL of a [SearchQuery] as cached by [DashDocSet::statement].
+#[derive(Debug, Clone)]
+struct Statements {
+    search: Arc<str>,
+    /// Only built for doc sets that have an `aliases` table.
+    aliases: Option<Arc<str>>,
+}
+
+impl SearchQuery {
+    fn sql(&self) -> String {
+        let relevance = match (self.fold_diacritics, self.signatures) {
+            (true, _) => diacritic_cases("name"),
+            (false, false) => relevance_cases("name", self.case_sensitive),
+            (false, true) => containment_case("name", self.case_sensitive, 1),
+        };
+        // Shorter declarations are likelier to be the bare symbol, try those first
+        let (order, limit) = match self.signatures || self.fold_diacritics {
+            false => ("relevance DESC", self.limit),
+            true => ("relevance DESC, length(name)", self.limit * SIGNATURE_OVERFETCH),
+        };
+        // Below every name bucket, so path matches only fill up what names leave of the limit.
+        // Rescored searches only keep name matches
+        let path_relevance = match self.search_paths && !self.fold_diacritics {
+            false => String::new(),
+            true => containment_case("path", self.case_sensitive, 20),
+        };
+
+        let source = self.schema.source();
+        let hidden_filter = self.hidden_filter();
+        format!(r"
+            WITH cte AS (
+                SELECT
+                    *,
+                    CASE {relevance}
+                        {path_relevance}
+                        ELSE 0
+                    END as relevance
+                FROM {source}
+            ),
+            -- The same symbol is often indexed under several pages/anchors, keep the shortest path only
+            deduped AS (
+                SELECT
+                    *,
+                    ROW_NUMBER() OVER (PARTITION BY name, type ORDER BY relevance DESC, length(path)) AS dup_rank
+                FROM cte
+                WHERE relevance > 0 {hidden_filter}
+            )
+            SELECT * FROM deduped WHERE dup_rank = 1 ORDER by {order} LIMIT {limit}
+        ")
+    }
+
+    /// Searches the `aliases(alias, target)` table some doc sets ship, `target` being the name
+    /// of the canonical entry. Rows are the target's, with the matched `alias` added.
+    /// Folding diacritics only filters aliases, like names, and binds the same patterns.
+    fn alias_sql(&self) -> String {
+        let relevance = match self.fold_diacritics {
+            false => relevance_cases("alias", self.case_sensitive),
+            true => diacritic_cases("alias"),
+        };
+        let source = self.schema.source();
+        let hidden_filter = self.hidden_filter();
+        format!(r"
+            WITH aliased AS (
+                SELECT s.*, a.alias AS alias
+                FROM aliases a
+                JOIN {source} s ON s.name = a.target
+            ),
+            scored AS (
+                SELECT
+                    *,
+                    CASE {relevance}
+                        ELSE 0
+                    END as relevance
+                FROM aliased
+            )
+            SELECT * FROM scored WHERE relevance > 0 {hidden_filter} ORDER BY relevance DESC, length(alias) LIMIT {}
+        ", self.limit)
+    }
+
+    /// `AND type NOT IN (...)` for the hidden types, the names inlined as SQL strings.
+    fn hidden_filter(&self) -> String {
+        if self.hidden_types.is_empty() {
+            return String::new();
+        }
+        let types: Vec<String> = self.hidden_types
+            .iter()
+            .map(|t| format!("'{}'", t.replace('\'', "''")))
+            .collect();
+        format!("AND type NOT IN ({})", types.join(", "))
+    }
+
+    async fn fetch_aliases(&self, doc_set: &DashDocSet, q: &str) -> sqlx::Result<Vec<SqliteRow>> {
+        match doc_set.statement(self).aliases {
+            Some(sql) => self.bind(&sql, q).fetch_all(&doc_set.db).await,
+            None => Ok(vec![]),
+        }
+    }
+
+    async fn fetch_all(&self, doc_set: &DashDocSet, q: &str) -> sqlx::Result<Vec<SqliteRow>> {
+        let sql = doc_set.statement(self).search;
+        self.bind(&sql, q).fetch_all(&doc_set.db).await
+    }
+
+    fn bind<'q>(&self, sql: &'q str, q: &str) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+        match self.fold_diacritics {
+            false => sqlx::query(sql).bind(q.to_string()),
+            true => sqlx::query(sql)
+                .bind(diacritic_pattern(q, false))
+                .bind(diacritic_pattern(q, true)),
+        }
+    }
+
+    /// Runs the search, retrying with a growing delay while another process (usually Zeal)
+    /// holds a lock on the index.
+    async fn fetch_all_retrying(&self, doc_set: &DashDocSet, q: &str) -> sqlx::Result<Vec<SqliteRow>> {
+        let mut delay = BUSY_RETRY_DELAY;
+        for _ in 0..BUSY_RETRIES {
+            match self.fetch_all(doc_set, q).await {
+                Err(e) if is_busy(&e) => {
+                    log::debug!("Index of {} is busy, retrying in {delay:?}", doc_set.name);
+                    sleep(delay).await;
+                    delay *= 2;
+                }
+                rs => return rs,
+            }
+        }
+        self.fetch_all(doc_set, q).await
+    }
+}
+
+/// The query `?1` as a LIKE pattern matching it literally: `%` and `_` typed in a query are
+/// part of the name searched, not wildcards. Patterns using it need `ESCAPE '\'`.
+const LIKE_QUERY: &str = r"replace(replace(replace(trim(?1), '\', '\\'), '%', '\%'), '_', '\_')";
+
+/// The relevance buckets of `column` against the query `?1`, as `WHEN` clauses of a `CASE`.
+///
+/// LIKE ignores case, so the case-sensitive buckets compare substrings instead. They keep the
+/// scores of their case-insensitive counterparts so results rank the same way.
+fn relevance_cases(column: &str, case_sensitive: bool) -> String {
+    match case_sensitive {
+        false => format!(r"
+                        WHEN {column} = trim(?1) THEN 100
+                        WHEN {column} = trim(?1) COLLATE NOCASE THEN 90
+                        WHEN {column} LIKE {LIKE_QUERY} || '%' ESCAPE '\' THEN 80
+                        WHEN {column} LIKE '%' || {LIKE_QUERY} ESCAPE '\' THEN 70
+                        WHEN {column} COLLATE NOCASE LIKE {LIKE_QUERY} || '%' ESCAPE '\' THEN 60
+                        WHEN {column} COLLATE NOCASE LIKE '%' || {LIKE_QUERY} ESCAPE '\' THEN 50"),
+        true => format!(r"
+                        WHEN {column} = trim(?1) THEN 100
+                        WHEN substr({column}, 1, length(trim(?1))) = trim(?1) THEN 80
+                        WHEN substr({column}, -length(trim(?1))) = trim(?1) THEN 70"),
+    }
+}
+
+/// A single bucket scoring `relevance` for any `column` containing the query.
+fn containment_case(column: &str, case_sensitive: bool, relevance: usize) -> String {
+    match case_sensitive {
+        false => format!(r"WHEN {column} LIKE '%' || {LIKE_QUERY} || '%' ESCAPE '\' THEN {relevance}"),
+        true => format!("WHEN instr({column}, trim(?1)) > 0 THEN {relevance}"),
+    }
+}
+
+/// Relevance 1 for any `column` matching the [diacritic_pattern]s bound as `?1` and `?2`, as
+/// `WHEN` clauses of a `CASE`.
+fn diacritic_cases(column: &str) -> String {
+    // Only values with a non-ASCII letter can carry an accent the query left out
+    format!(r"
+                WHEN {column} LIKE ?1 ESCAPE '\' THEN 1
+                WHEN {column} GLOB '*[^ -~]*' AND {column} LIKE ?2 ESCAPE '\' THEN 1")
+}
+
+/// Letters commonly carrying an accent, `e` standing for `é`, `è`, `ê` and `ë`.
+const ACCENTED_LETTERS: &str = "aeiouycnsz";
+
+/// A LIKE pattern for names containing `q` whatever their accents. Letters the query has an
+/// accent on match any single character, so `café` finds `cafe`. A `loose` pattern also does so
+/// for every letter that often carries one, so `cafe` finds `café`. Needs `ESCAPE '\'`. This
+/// only narrows the candidates down, they're matched against the folded query afterwards.
+fn diacritic_pattern(q: &str, loose: bool) -> String {
+    let mut pattern = String::from("%");
+    // Composed, so an accent is part of its letter rather than a character of its own
+    for c in q.trim().nfc() {
+        let accented = strip_diacritics(c.encode_utf8(&mut [0; 4])).chars().ne([c]);
+        match c {
+            '%' | '_' | '\\' => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            _ if accented => pattern.push('_'),
+            c if loose && ACCENTED_LETTERS.contains(c.to_ascii_lowercase()) => pattern.push('_'),
+            c => pattern.push(c),
+        }
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Signature-style names are ranked after fetching, so more candidates than the limit are
+/// fetched to leave room for the ones that end up dropped.
+const SIGNATURE_OVERFETCH: usize = 4;
+
+/// The bare symbol declared by a signature-style name, e.g. `push` for
+/// `fn push(&mut self, value: T)` or `strdup` for `char *strdup(const char *s)`. Names that
+/// aren't declarations come back as they are.
+fn signature_symbol(name: &str) -> &str {
+    let head = name.split(['(', '<', '[']).next().unwrap_or(name);
+    let symbol = head
+        .split_whitespace()
+        .last()
+        .map_or(head, |s| s.trim_start_matches(['*', '&']).trim_end_matches(':'));
+    match symbol.is_empty() {
+        true => name,
+        false => symbol,
+    }
+}
+
+const BUSY_RETRIES: usize = 3;
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(10);
+/// How long SQLite itself waits on a lock before reporting the index busy.
+const BUSY_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// Whether `e` is SQLite reporting the database busy or locked, extended codes included.
+fn is_busy(e: &sqlx::Error) -> bool {
+    const SQLITE_BUSY: i32 = 5;
+    const SQLITE_LOCKED: i32 = 6;
+    match e {
+        sqlx::Error::Database(e) => e
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED)),
+        _ => false,
+    }
+}
+
+/// Layout of a doc set index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum IndexSchema {
+    /// A plain `searchIndex(name, type, path)` table, what most doc sets ship.
+    SearchIndex,
+    /// Core Data tables (`ZTOKEN` and friends) used by Apple doc sets, which also carry a
+    /// declaration and abstract for many entries.
+    CoreData,
+}
+
+impl IndexSchema {
+    async fn detect(db: &SqlitePool) -> sqlx::Result<Self> {
+        let core_data = sqlx::query("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'ZTOKEN'")
+            .fetch_optional(db)
+            .await?
+            .is_some();
+        Ok(match core_data {
+            true => IndexSchema::CoreData,
+            false => IndexSchema::SearchIndex,
+        })
+    }
+
+    /// The rows searched, as `name`, `type`, `path` and optionally `fragment` and `description`.
+    fn source(&self) -> &'static str {
+        match self {
+            IndexSchema::SearchIndex => "searchIndex",
+            IndexSchema::CoreData => r"(
+                SELECT
+                    t.ZTOKENNAME AS name,
+                    ty.ZTYPENAME AS type,
+                    f.ZPATH AS path,
+                    m.ZANCHOR AS fragment,
+                    COALESCE(NULLIF(m.ZABSTRACT, ''), m.ZDECLARATION, '') AS description
+                FROM ZTOKEN t
+                JOIN ZTOKENTYPE ty ON ty.Z_PK = t.ZTOKENTYPE
+                JOIN ZTOKENMETAINFORMATION m ON m.Z_PK = t.ZMETAINFORMATION
+                JOIN ZFILEPATH f ON f.Z_PK = m.ZFILE
+            )",
+        }
+    }
+}
+
+/// Characters escaped in a fragment. Existing `%XX` escapes are kept as they are.
+const FRAGMENT_ESCAPES: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
+
+/// An entry URL as stored in `searchIndex`: a path relative to the documents that may or may
+/// not be percent-encoded, followed by an optional `?query` and `#fragment`.
+struct EntryUrl<'a> {
+    /// Percent-decoded, ready for filesystem lookups.
+    path: Cow<'a, str>,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+}
+
+impl<'a> EntryUrl<'a> {
+    fn parse(entry_url: &'a str) -> Self {
+        let (rest, fragment) = match entry_url.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (entry_url, None),
+        };
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+
+        Self {
+            path: percent_decode_str(path).decode_utf8_lossy(),
+            query: query.filter(|q| !q.is_empty()),
+            fragment: fragment.filter(|f| !f.is_empty()),
         }
     }
 }
 
+/// Whether the desktop has a handler for `dash-plugin://` URLs, normally Zeal.
+async fn handles_dash_plugin() -> bool {
+    spawn_blocking(|| {
+        std::process::Command::new("xdg-mime")
+            .args(["query", "default", "x-scheme-handler/dash-plugin"])
+            .output()
+            .is_ok_and(|out| out.status.success() && out.stdout.iter().any(|b| !b.is_ascii_whitespace()))
+    })
+        .await
+        .unwrap_or(false)
+}
+
+fn is_web_url(entry_url: &str) -> bool {
+    ["http://", "https://"]
+        .iter()
+        .any(|scheme| entry_url.get(..scheme.len()).is_some_and(|s| s.eq_ignore_ascii_case(scheme)))
+}
+
+/// Joins a `searchIndex` row's `path` and `fragment` columns. Some doc sets keep the anchor in
+/// `path` itself (`foo.html#bar`), that anchor wins and is never followed by a second one.
+fn entry_url(path: &str, fragment: Option<&str>) -> String {
+    let (file, anchor) = match path.split_once('#') {
+        Some((file, anchor)) => (file, Some(anchor).filter(|a| !a.is_empty())),
+        None => (path, None),
+    };
+    match anchor.or(fragment) {
+        Some(anchor) => format!("{file}#{anchor}"),
+        None => file.to_string(),
+    }
+}
+
+fn file_url(file: &Path, query: Option<&str>, fragment: Option<&str>) -> String {
+    with_query_and_fragment(opener::file_url(file), query, fragment)
+}
+
+fn with_query_and_fragment(mut url: String, query: Option<&str>, fragment: Option<&str>) -> String {
+    if let Some(query) = query {
+        url.push('?');
+        url.push_str(query);
+    }
+    if let Some(fragment) = fragment {
+        url.push('#');
+        url.extend(utf8_percent_encode(fragment, FRAGMENT_ESCAPES));
+    }
+    url
+}
+
+/// Opens a doc set index for the read-only, repeated prefix queries issued on every keystroke.
+///
+/// Searches are sequential per doc set so a couple of connections are plenty. The cache and mmap
+/// pragmas keep the hot pages of the index in memory between keystrokes, so repeated queries
+/// against the same doc set stop paying for file reads after the first one.
+async fn open_index_db(path: &Path) -> sqlx::Result<SqlitePool> {
+    let options = SqliteConnectOptions::default()
+        .filename(path)
+        .read_only(true)
+        .busy_timeout(BUSY_TIMEOUT)
+        .pragma("query_only", "ON")
+        .pragma("cache_size", "-8192")
+        .pragma("mmap_size", "67108864")
+        .pragma("temp_store", "MEMORY");
+
+    SqlitePoolOptions::new()
+        .min_connections(0)
+        .max_connections(2)
+        .connect_with(options)
+        .await
+}
+
+/// An alias ranks just below the same match on the canonical name.
+const ALIAS_RELEVANCE_PENALTY: usize = 5;
+
+fn to_search_entry(row: &SqliteRow) -> SearchEntry {
+    let name: &str = row.get("name");
+    let path: &str = row.get("path");
+    let fragment = row.try_get::<&str, _>("fragment").ok().filter(|f| !f.is_empty());
+    SearchEntry {
+        entry_type: row.get::<&str, _>("type").parse().unwrap(),
+        title: name.into(),
+        desc: row.try_get::<&str, _>("description").map(Arc::from).unwrap_or_else(|_| Arc::from("")),
+        id: entry_url(path, fragment).into(),
+        relevance: row.get::<i64, _>("relevance") as usize,
+    }
+}
+
 #[async_trait]
 impl DocProvider for Dash {
     fn name(&self) -> &str {
         "Dash"
     }
 
+    fn icon(&self) -> &str {
+        "zeal"
+    }
+
+    async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>> {
+        Ok(self.loaded().doc_sets.iter().map(DashDocSet::to_doc_set).collect())
+    }
+
     async fn search_doc_sets(&self, keyword: &str) -> anyhow::Result<Vec<DocSet>> {
-        let keyword = keyword.to_ascii_lowercase();
+        let keyword = keyword.trim().to_lowercase();
 
-        let rs = self.doc_sets
-            .iter()
-            .filter(|set| set.contains_keyword(&keyword))
-            .map(DashDocSet::to_doc_set)
+        let loaded = self.loaded();
+        let mut rs: Vec<DocSet> = loaded.keyword_index
+            .lookup(&keyword)
+            .into_iter()
+            .map(|i| loaded.doc_sets[i].to_doc_set())
             .collect();
-        log::debug!("DocSet search result for q = {keyword}: {rs:?}");
+        sort_by_keyword(&mut rs, &keyword);
+        debug_throttled!("DocSet search result for q = {keyword}: {rs:?}");
         Ok(rs)
     }
 
-    async fn search(&self, doc_set_id: &str, q: &str) -> anyhow::Result<Vec<SearchEntry>> {
-        let doc_set = match self.doc_sets.iter().find(|ds| ds.name.as_ref().eq(doc_set_id)) {
+    fn supports_keyword(&self, keyword: &str) -> bool {
+        self.loaded().keyword_index.contains(&keyword.trim().to_lowercase())
+    }
+
+    async fn search(&self, doc_set_id: &str, q: &str, options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>> {
+        let loaded = self.loaded();
+        let doc_set = match loaded.doc_set(doc_set_id) {
             Some(v) => v,
             None => return Ok(vec![]),
         };
 
-        let entries: Vec<SqliteRow> = sqlx::query(r"
-            WITH cte AS (
-                SELECT
-                    *,
-                    CASE
-                        WHEN name = trim(?1) THEN 100
-                        WHEN name = trim(?1) COLLATE NOCASE THEN 90
-                        WHEN name LIKE trim(?1) || '%' THEN 80
-                        WHEN name LIKE '%' || trim(?1) THEN 70
-                        WHEN name COLLATE NOCASE LIKE trim(?1) || '%' THEN 60
-                        WHEN name COLLATE NOCASE LIKE '%' || trim(?1) THEN 50
-                        ELSE 0
-                    END as relevance
-                FROM searchIndex
-            )
-            SELECT * FROM cte WHERE relevance > 0 ORDER by relevance DESC LIMIT 30
-        ")
-            .bind(q)
-            .fetch_all(&doc_set.db).await.context("Running search SQL")?;
-        log::debug!("Searching for {q} got {} results", entries.len());
-        Ok(entries.into_iter().map(|row| {
-            let name: &str = row.get("name");
-            let path: &str = row.get("path");
-            let fragment: Option<&str> = row.try_get("fragment").ok();
-            SearchEntry {
-                entry_type: row.get::<&str, _>("type").parse().unwrap(),
-                title: name.into(),
-                desc: Arc::from(""),
-                id: serde_json::to_string(&EntryId {
-                    name,
-                    path,
-                    fragment,
-                }).unwrap().into(),
-                relevance: row.get::<i64, _>("relevance") as usize,
+        let limit = doc_set.result_limit.map_or(options.limit, |l| l.min(options.limit));
+        let query = SearchQuery {
+            schema: doc_set.schema,
+            limit,
+            case_sensitive: options.case_sensitive,
+            search_paths: self.search_paths,
+            signatures: doc_set.normalize_signatures,
+            fold_diacritics: options.fold_diacritics,
+            hidden_types: options.hidden_types.iter().map(|t| Arc::from(t.as_str())).collect(),
+        };
+        let entries = match query.fetch_all_retrying(doc_set, q).await {
+            Ok(v) => v,
+            Err(e) if is_busy(&e) => {
+                log::warn!("Skipping doc set {doc_set_id}, its index stayed locked: {e}");
+                return Ok(vec![]);
             }
-        }).collect())
+            Err(e) => return Err(e).context("Running search SQL"),
+        };
+        debug_throttled!("Searching for {q} got {} results", entries.len());
+        let mut entries: Vec<SearchEntry> = entries.iter().map(to_search_entry).collect();
+        if doc_set.normalize_signatures || options.fold_diacritics {
+            let q = options.fold(q.trim());
+            entries = entries
+                .into_iter()
+                .filter_map(|e| {
+                    let name = match doc_set.normalize_signatures {
+                        true => signature_symbol(&e.title),
+                        false => &e.title,
+                    };
+                    Some(SearchEntry {
+                        relevance: name_relevance(&options.fold(name), &q)?,
+                        ..e
+                    })
+                })
+                .collect();
+        }
+
+        if doc_set.has_aliases {
+            match query.fetch_aliases(doc_set, q).await {
+                Ok(rows) => entries.extend(rows.iter().filter_map(|row| {
+                    let entry = to_search_entry(row);
+                    let alias = row.get::<&str, _>("alias");
+                    let relevance = match options.fold_diacritics {
+                        true => name_relevance(&options.fold(alias), &options.fold(q.trim()))?,
+                        false => entry.relevance,
+                    };
+                    Some(SearchEntry {
+                        title: alias.into(),
+                        desc: format!("(alias of {})", entry.title).into(),
+                        relevance: relevance.saturating_sub(ALIAS_RELEVANCE_PENALTY),
+                        ..entry
+                    })
+                })),
+                Err(e) => log::warn!("Skipping aliases of doc set {doc_set_id}: {e}"),
+            }
+        }
+
+        doc_set.scorer.rescore(q, &mut entries);
+        entries.sort_by_key(|e| std::cmp::Reverse(e.relevance));
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    async fn open(&self, doc_set_id: &str, entry_url: &str) -> anyhow::Result<()> {
+        log::debug!("Opening entry {entry_url} for doc_set {doc_set_id}");
+        let loaded = self.loaded();
+        let doc_set = loaded.doc_set(doc_set_id).with_context(|| format!("Unknown doc set {doc_set_id}"))?;
+        match self.open_mode {
+            OpenMode::Zeal => match doc_set.zeal_url(entry_url).await {
+                Ok(url) if handles_dash_plugin().await => return opener::open(&url),
+                Ok(_) => log::warn!("Nothing handles dash-plugin:// URLs, opening {entry_url} in the browser"),
+                Err(e) => log::warn!("Unable to open {entry_url} in Zeal, opening it in the browser: {e:?}"),
+            },
+            OpenMode::Server if !is_web_url(entry_url) => match doc_set.served_url(&self.server, entry_url).await {
+                Ok(url) => return opener::open(&url),
+                Err(e) => log::warn!("Unable to serve {entry_url}, opening the file instead: {e:?}"),
+            },
+            _ => {}
+        }
+        opener::open(&doc_set.resolve_url(entry_url)?)
+    }
+
+    async fn resolve_url(&self, doc_set_id: &str, entry_url: &str) -> Option<String> {
+        self.loaded().doc_set(doc_set_id)?.resolve_url(entry_url).ok()
+    }
+
+    async fn entry_path(&self, doc_set_id: &str, entry_url: &str) -> anyhow::Result<PathBuf> {
+        let loaded = self.loaded();
+        let doc_set = loaded.doc_set(doc_set_id).with_context(|| format!("Unknown doc set {doc_set_id}"))?;
+        doc_set.resolve_path(entry_url)
+    }
+
+    async fn open_doc_set(&self, doc_set_id: &str) -> anyhow::Result<()> {
+        let loaded = self.loaded();
+        let doc_set = loaded.doc_set(doc_set_id).with_context(|| format!("Unknown doc set {doc_set_id}"))?;
+        let index = doc_set.resolve_index().await?;
+        opener::open(&file_url(&index, None, None))
     }
 
-    async fn open(&self, doc_set_id: &str, entry_id: &str) -> anyhow::Result<()> {
-        let entry_id: EntryId = serde_json::from_str(entry_id).context("Parsing ID")?;
-        log::debug!("Opening entry {entry_id:#?} for doc_set {doc_set_id}");
+    async fn status(&self) -> anyhow::Result<ProviderStatus> {
+        let loaded = self.loaded();
+        let mut doc_sets = vec![];
+        for ds in &loaded.doc_sets {
+            doc_sets.push(ds.check_health().await);
+        }
 
-        // web_view::builder()
-        //     .title(&format!("Documentation for {doc_set_id}"))
-        //     .content(Content::Url(entry_url.as_ref()))
-        //     .resizable(true)
-        //     .user_data(())
-        //     .invoke_handler(|_wv, _arg| Ok(()))
-        //     .run()?;
+        Ok(ProviderStatus {
+            provider: self.name().to_string(),
+            roots: vec![loaded.root.clone()],
+            loaded: loaded.doc_sets.len(),
+            skipped: loaded.skipped,
+            doc_sets,
+            error: None,
+        })
+    }
+
+    async fn check(&self) -> anyhow::Result<Vec<DocSetCheck>> {
+        let loaded = self.loaded();
+        let mut rs = vec![];
+        for ds in &loaded.doc_sets {
+            rs.push(ds.check().await);
+        }
+        Ok(rs)
+    }
+
+    /// Rescans the doc set root, picking up installed and removed doc sets. Doc sets still there
+    /// are opened again, their old pools close once the last search using them is done.
+    async fn reload(&self) -> anyhow::Result<()> {
+        let loaded = Loaded::load(&self.config).await?;
+        log::info!("Reloaded {} Dash doc sets", loaded.doc_sets.len());
+        *self.loaded.write().unwrap() = Arc::new(loaded);
         Ok(())
     }
 
-    async fn clean_up(&self) {}
+    async fn clean_up(&self) -> anyhow::Result<()> {
+        // Index pools stay open, closing a sqlx pool is final and idle connections already time
+        // out on their own. The doc server keeps serving the pages KRunner opened before closing
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::ConnectOptions;
+    use tempfile::TempDir;
+
+    use crate::provider::EntryType;
+
+    use super::*;
+
+    /// A doc set folder under `root` named `name`, its index built by running `statements`.
+    async fn fixture_with(root: &Path, name: &str, statements: &[String]) -> DashDocSet {
+        let path = root.join(format!("{name}.docset"));
+        std::fs::create_dir_all(&path).unwrap();
+        let options = SqliteConnectOptions::new()
+            .filename(path.join("docSet.dsidx"))
+            .create_if_missing(true);
+        let db = SqlitePool::connect_with(options).await.unwrap();
+        for statement in statements {
+            sqlx::query(statement).execute(&db).await.unwrap();
+        }
+        db.close().await;
+
+        let meta = DocSetMeta {
+            name: name.into(),
+            title: name.into(),
+            keywords: vec![name.to_lowercase()],
+            icon: None,
+            index_file: None,
+            homepage: None,
+            result_limit: None,
+        };
+        DashDocSet::open(&path, name.into(), meta, &DashConfig::default()).await.unwrap()
+    }
+
+    /// A doc set with a plain `searchIndex` holding `rows` of (name, type, path).
+    async fn fixture(root: &Path, name: &str, rows: &[(&str, &str, &str)]) -> DashDocSet {
+        let mut statements = vec!["CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT)".to_string()];
+        statements.extend(rows.iter().map(|(name, entry_type, path)| format!(
+            "INSERT INTO searchIndex(name, type, path) VALUES ({}, {}, {})",
+            sql_string(name),
+            sql_string(entry_type),
+            sql_string(path),
+        )));
+        fixture_with(root, name, &statements).await
+    }
+
+    /// Loads the doc sets under `root`, without the metadata cache.
+    async fn load(root: &Path) -> anyhow::Result<Loaded> {
+        let config = DashConfig { root: Some(root.to_path_buf()), cache_metadata: false, ..Default::default() };
+        Loaded::load(&config).await
+    }
+
+    fn write_meta(doc_set: &Path, name: &str) {
+        std::fs::write(doc_set.join("meta.json"), format!(r#"{{"name": "{name}"}}"#)).unwrap();
+    }
+
+    fn sql_string(s: &str) -> String {
+        format!("'{}'", s.replace('\'', "''"))
+    }
+
+    fn dash(doc_sets: Vec<DashDocSet>) -> Dash {
+        let keyword_index = KeywordIndex::new(&doc_sets);
+        Dash {
+            config: Default::default(),
+            open_mode: Default::default(),
+            search_paths: false,
+            loaded: RwLock::new(Arc::new(Loaded {
+                root: PathBuf::new(),
+                doc_sets,
+                keyword_index,
+                skipped: 0,
+            })),
+            server: Default::default(),
+        }
+    }
+
+    async fn search(dash: &Dash, q: &str, options: &SearchOptions) -> Vec<(String, usize)> {
+        dash.search("Rust", q, options)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.title.to_string(), e.relevance))
+            .collect()
+    }
+
+    #[test]
+    fn keyword_index_agrees_with_a_linear_scan() {
+        // Keywords over a small alphabet, so many of them share prefixes
+        let mut seed = 7u32;
+        let mut next = |n: u32| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) % n
+        };
+        let mut doc_sets: Vec<Vec<Arc<str>>> = vec![];
+        for _ in 0..200 {
+            let mut keywords = vec![];
+            for _ in 0..=next(3) {
+                let keyword: String = (0..=next(4)).map(|_| char::from(b'a' + next(3) as u8)).collect();
+                keywords.push(Arc::from(keyword));
+            }
+            doc_sets.push(keywords);
+        }
+        let index = KeywordIndex::from_keywords(doc_sets.iter().map(Vec::as_slice));
+
+        // Every keyword of up to 5 letters, plus ones no doc set has
+        let mut typed = vec![String::new(), "d".to_string(), "abd".to_string()];
+        let mut level = vec![String::new()];
+        for _ in 0..5 {
+            level = level.iter().flat_map(|p| ["a", "b", "c"].map(|c| format!("{p}{c}"))).collect();
+            typed.extend(level.iter().cloned());
+        }
+        for kw in typed {
+            let linear: Vec<usize> = match kw.is_empty() {
+                true => vec![],
+                false => (0..doc_sets.len()).filter(|i| doc_sets[*i].iter().any(|k| k.starts_with(&kw))).collect(),
+            };
+            assert_eq!(index.lookup(&kw), linear, "keyword {kw:?}");
+        }
+    }
+
+    #[test]
+    fn typed_keywords_only_match_as_prefixes() {
+        let keywords: Vec<Vec<Arc<str>>> = vec![vec!["python".into()], vec!["c".into(), "cpp".into()]];
+        let index = KeywordIndex::from_keywords(keywords.iter().map(Vec::as_slice));
+
+        assert_eq!(index.lookup("py"), vec![0]);
+        assert_eq!(index.lookup("python"), vec![0]);
+        assert!(index.lookup("pythonista").is_empty());
+        assert_eq!(index.lookup("c"), vec![1]);
+        assert!(index.lookup("").is_empty());
+    }
+
+    #[test]
+    fn entry_urls_only_carry_a_fragment_when_there_is_one() {
+        assert_eq!(entry_url("vec.html", None), "vec.html");
+        assert_eq!(entry_url("vec.html", Some("method.push")), "vec.html#method.push");
+        assert_eq!(entry_url("vec.html#", None), "vec.html");
+        assert_eq!(entry_url("vec.html#method.push", Some("method.pop")), "vec.html#method.push");
+    }
+
+    #[tokio::test]
+    async fn resolves_entries_with_and_without_fragment() {
+        let dir = TempDir::new().unwrap();
+        let doc_set = fixture_with(dir.path(), "Rust", &[
+            "CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT, fragment TEXT)",
+            "INSERT INTO searchIndex(name, type, path, fragment) VALUES \
+                ('Vec', 'Struct', 'vec.html', NULL), \
+                ('push', 'Method', 'vec.html', 'method.push')",
+        ].map(String::from)).await;
+        let page = doc_set.resource_root.join("vec.html");
+        std::fs::write(&page, "").unwrap();
+        let dash = dash(vec![doc_set]);
+
+        let vec = dash.search("Rust", "Vec", &Default::default()).await.unwrap();
+        assert_eq!(vec[0].id.as_ref(), "vec.html");
+        let push = dash.search("Rust", "push", &Default::default()).await.unwrap();
+        assert_eq!(push[0].id.as_ref(), "vec.html#method.push");
+
+        let url = file_url(&page, None, None);
+        assert_eq!(dash.resolve_url("Rust", "vec.html").await, Some(url.clone()));
+        assert_eq!(dash.resolve_url("Rust", "vec.html#method.push").await, Some(format!("{url}#method.push")));
+        assert_eq!(dash.entry_path("Rust", "vec.html#method.push").await.unwrap(), page);
+    }
+
+    #[tokio::test]
+    async fn decodes_entry_paths_and_encodes_them_back_into_urls() {
+        let dir = TempDir::new().unwrap();
+        let doc_set = fixture(dir.path(), "Rust", &[]).await;
+        let page = doc_set.resource_root.join("my page.html");
+        std::fs::write(&page, "").unwrap();
+        let url = file_url(&page, None, None);
+        assert!(url.ends_with("/my%20page.html"), "{url}");
+
+        assert_eq!(doc_set.resolve_path("my page.html").unwrap(), page);
+        assert_eq!(doc_set.resolve_path("my%20page.html").unwrap(), page);
+        assert_eq!(doc_set.resolve_url("my page.html#method.push").unwrap(), format!("{url}#method.push"));
+        assert_eq!(doc_set.resolve_url("my%20page.html?v=2#Vec.new").unwrap(), format!("{url}?v=2#Vec.new"));
+        assert_eq!(doc_set.resolve_url("my%20page.html#a b").unwrap(), format!("{url}#a%20b"));
+    }
+
+    #[tokio::test]
+    async fn opens_web_pages_as_they_are_and_files_by_url() {
+        let dir = TempDir::new().unwrap();
+        let doc_set = fixture(dir.path(), "Rust", &[]).await;
+        let page = doc_set.resource_root.join("vec.html");
+        std::fs::write(&page, "").unwrap();
+
+        let online = "https://doc.rust-lang.org/std/vec/struct.Vec.html#method.push";
+        assert_eq!(doc_set.resolve_url(online).unwrap(), online);
+        assert!(doc_set.resolve_path(online).is_err());
+        assert_eq!(doc_set.resolve_url("vec.html").unwrap(), file_url(&page, None, None));
+    }
+
+    #[tokio::test]
+    async fn asks_zeal_for_the_entry_at_a_path() {
+        let dir = TempDir::new().unwrap();
+        let doc_set = fixture(dir.path(), "Rust", &[("Vec<T>", "Struct", "vec.html")]).await;
+
+        assert_eq!(doc_set.zeal_url("vec.html#method.push").await.unwrap(), "dash-plugin://keys=rust&query=Vec%3CT%3E");
+        assert!(doc_set.zeal_url("string.html").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn ranks_names_into_relevance_buckets() {
+        let dir = TempDir::new().unwrap();
+        let dash = dash(vec![fixture(dir.path(), "Rust", &[
+            ("SmallVecMap", "Struct", "small_vec_map.html"),
+            ("BitVec", "Struct", "bit_vec.html"),
+            ("Vector", "Class", "vector.html"),
+            ("vec", "Module", "vec/index.html"),
+            ("Vec", "Struct", "vec/struct.Vec.html"),
+            ("String", "Struct", "string/struct.String.html"),
+        ]).await]);
+
+        // LIKE ignores ASCII case, so prefix and suffix matches land in their upper bucket
+        // whatever the case
+        assert_eq!(search(&dash, "Vec", &Default::default()).await, vec![
+            ("Vec".to_string(), 100),
+            ("vec".to_string(), 90),
+            ("Vector".to_string(), 80),
+            ("BitVec".to_string(), 70),
+        ]);
+        assert_eq!(search(&dash, "vector", &Default::default()).await, vec![("Vector".to_string(), 90)]);
+        assert!(search(&dash, "Deque", &Default::default()).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn case_sensitive_search_only_keeps_exact_case_buckets() {
+        let dir = TempDir::new().unwrap();
+        let dash = dash(vec![fixture(dir.path(), "Rust", &[
+            ("Vec", "Struct", "vec/struct.Vec.html"),
+            ("vec", "Module", "vec/index.html"),
+            ("Vector", "Class", "vector.html"),
+            ("vector", "Function", "fn.vector.html"),
+            ("BitVec", "Struct", "bit_vec.html"),
+            ("bitvec", "Module", "bitvec/index.html"),
+        ]).await]);
+
+        let case_sensitive = SearchOptions { case_sensitive: true, ..Default::default() };
+        assert_eq!(search(&dash, "Vec", &case_sensitive).await, vec![
+            ("Vec".to_string(), 100),
+            ("Vector".to_string(), 80),
+            ("BitVec".to_string(), 70),
+        ]);
+        assert_eq!(search(&dash, "vector", &case_sensitive).await, vec![("vector".to_string(), 100)]);
+
+        let mut insensitive = search(&dash, "vector", &Default::default()).await;
+        insensitive.sort();
+        assert_eq!(insensitive, vec![("Vector".to_string(), 90), ("vector".to_string(), 100)]);
+    }
+
+    #[tokio::test]
+    async fn matches_like_wildcards_literally() {
+        let dir = TempDir::new().unwrap();
+        let dash = dash(vec![fixture(dir.path(), "Rust", &[
+            ("foo_bar", "Function", "foo_bar.html"),
+            ("fooxbar", "Function", "fooxbar.html"),
+            ("100%", "Guide", "percent.html"),
+            ("1000", "Constant", "thousand.html"),
+            (r"a\b", "Guide", "backslash.html"),
+            ("ab", "Guide", "ab.html"),
+        ]).await]);
+
+        assert_eq!(search(&dash, "foo_bar", &Default::default()).await, vec![("foo_bar".to_string(), 100)]);
+        assert_eq!(search(&dash, "%", &Default::default()).await, vec![("100%".to_string(), 70)]);
+        assert_eq!(search(&dash, r"\b", &Default::default()).await, vec![(r"a\b".to_string(), 70)]);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_shortest_path_of_duplicate_entries() {
+        let dir = TempDir::new().unwrap();
+        let dash = dash(vec![fixture(dir.path(), "Rust", &[
+            ("Vec", "Struct", "alloc/vec/struct.Vec.html"),
+            ("Vec", "Struct", "std/vec/struct.Vec.html"),
+            ("Vec", "Guide", "book/vectors.html"),
+        ]).await]);
+
+        let mut entries = dash.search("Rust", "Vec", &Default::default()).await.unwrap();
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        let ids: Vec<&str> = entries.iter().map(|e| e.id.as_ref()).collect();
+        assert_eq!(ids, vec!["book/vectors.html", "std/vec/struct.Vec.html"]);
+    }
+
+    #[tokio::test]
+    async fn resolves_the_declared_index_then_index_html_then_the_first_page() {
+        let dir = TempDir::new().unwrap();
+        let mut doc_set = fixture(dir.path(), "Rust", &[]).await;
+        let documents = dir.path().join("Documents");
+        for page in ["guide/intro.html", "api/vec.html", "api/notes.txt"] {
+            let path = documents.join(page);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        doc_set.resource_root = documents.clone();
+
+        assert_eq!(doc_set.resolve_index().await.unwrap(), documents.join("api/vec.html"));
+        std::fs::write(documents.join("index.html"), "").unwrap();
+        assert_eq!(doc_set.resolve_index().await.unwrap(), documents.join("index.html"));
+        doc_set.index_file = Some("guide/intro.html#top".into());
+        assert_eq!(doc_set.resolve_index().await.unwrap(), documents.join("guide/intro.html"));
+    }
+
+    #[tokio::test]
+    async fn skips_doc_sets_without_an_index() {
+        let dir = TempDir::new().unwrap();
+        fixture(dir.path(), "Rust", &[("Vec", "Struct", "vec.html")]).await;
+        write_meta(&dir.path().join("Rust.docset"), "Rust");
+        let broken = dir.path().join("Broken.docset");
+        std::fs::create_dir(&broken).unwrap();
+        write_meta(&broken, "Broken");
+        std::fs::create_dir(dir.path().join("notes")).unwrap();
+
+        let loaded = load(dir.path()).await.unwrap();
+        let names: Vec<&str> = loaded.doc_sets.iter().map(|ds| ds.name.as_ref()).collect();
+        assert_eq!(names, vec!["Rust"]);
+        assert_eq!(loaded.skipped, 1);
+    }
+
+    #[tokio::test]
+    async fn a_missing_root_loads_nothing_but_an_unreadable_one_fails() {
+        let dir = TempDir::new().unwrap();
+        let loaded = load(&dir.path().join("docsets")).await.unwrap();
+        assert!(loaded.doc_sets.is_empty());
+
+        // Listing a file fails like listing a folder we may not read
+        let file = dir.path().join("docsets.txt");
+        std::fs::write(&file, "").unwrap();
+        assert!(load(&file).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn loads_doc_sets_with_only_an_info_plist() {
+        let dir = TempDir::new().unwrap();
+        fixture(dir.path(), "iOS", &[("UIView", "Class", "uiview.html")]).await;
+        let path = dir.path().join("iOS.docset");
+        let contents = path.join("Contents");
+        std::fs::create_dir_all(contents.join("Resources")).unwrap();
+        std::fs::rename(path.join("docSet.dsidx"), contents.join("Resources").join("docSet.dsidx")).unwrap();
+        std::fs::write(contents.join("Info.plist"), r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleName</key>
+    <string>iOS</string>
+    <key>DocSetPlatformFamily</key>
+    <string>iphoneos</string>
+</dict>
+</plist>
+"#).unwrap();
+
+        let dash = dash(load(dir.path()).await.unwrap().doc_sets);
+        let doc_sets = dash.search_doc_sets("iphoneos").await.unwrap();
+        assert_eq!(doc_sets.iter().map(|ds| ds.name.as_ref()).collect::<Vec<_>>(), vec!["iOS"]);
+        let entries = dash.search(&doc_sets[0].id, "UIView", &Default::default()).await.unwrap();
+        assert_eq!(entries[0].title.as_ref(), "UIView");
+    }
+
+    #[test]
+    fn reads_the_homepage_of_meta_json_when_there_is_one() {
+        let homepage = |meta: Value| DocSetMeta::parse(Path::new("Rust.docset"), meta).unwrap().homepage;
+        assert_eq!(homepage(serde_json::json!({"name": "Rust"})), None);
+        assert_eq!(
+            homepage(serde_json::json!({"name": "Rust", "homepage": "https://github.com/rust-lang/rust"})),
+            Some("https://github.com/rust-lang/rust".to_string()),
+        );
+        assert_eq!(
+            homepage(serde_json::json!({"name": "Rust", "extra": {"repository": "https://github.com/rust-lang/rust"}})),
+            Some("https://github.com/rust-lang/rust".to_string()),
+        );
+        // Only web pages can be opened in the browser
+        assert_eq!(homepage(serde_json::json!({"name": "Rust", "homepage": "javascript:alert(1)"})), None);
+    }
+
+    #[tokio::test]
+    async fn loads_symlinked_doc_sets_from_where_they_live() {
+        let dir = TempDir::new().unwrap();
+        let elsewhere = dir.path().join("elsewhere");
+        fixture(&elsewhere, "Rust", &[("Vec", "Struct", "vec.html")]).await;
+        write_meta(&elsewhere.join("Rust.docset"), "Rust");
+        let root = dir.path().join("docsets");
+        std::fs::create_dir(&root).unwrap();
+        std::os::unix::fs::symlink(elsewhere.join("Rust.docset"), root.join("Rust.docset")).unwrap();
+
+        let loaded = load(&root).await.unwrap();
+        assert_eq!(loaded.doc_sets[0].resource_root, elsewhere.join("Rust.docset").canonicalize().unwrap());
+        let dash = dash(loaded.doc_sets);
+        assert_eq!(dash.search("Rust.docset", "Vec", &Default::default()).await.unwrap()[0].title.as_ref(), "Vec");
+    }
+
+    #[tokio::test]
+    async fn doc_sets_sharing_a_name_are_told_apart_by_folder() {
+        let dir = TempDir::new().unwrap();
+        fixture(dir.path(), "Rust", &[("Vec", "Struct", "vec.html")]).await;
+        fixture(dir.path(), "Rust-nightly", &[("Vec", "Struct", "nightly/vec.html")]).await;
+        write_meta(&dir.path().join("Rust.docset"), "Rust");
+        write_meta(&dir.path().join("Rust-nightly.docset"), "Rust");
+
+        let dash = dash(load(dir.path()).await.unwrap().doc_sets);
+        let mut doc_sets: Vec<(String, String)> = dash.doc_sets()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|ds| (ds.id.to_string(), ds.name.to_string()))
+            .collect();
+        doc_sets.sort();
+        assert_eq!(doc_sets, vec![
+            ("Rust-nightly.docset".to_string(), "Rust".to_string()),
+            ("Rust.docset".to_string(), "Rust".to_string()),
+        ]);
+        for (id, path) in [("Rust.docset", "vec.html"), ("Rust-nightly.docset", "nightly/vec.html")] {
+            let rs = dash.search(id, "Vec", &Default::default()).await.unwrap();
+            assert_eq!(rs.iter().map(|e| e.id.as_ref()).collect::<Vec<_>>(), vec![path], "searching {id}");
+        }
+    }
+
+    #[tokio::test]
+    async fn honors_the_result_limit_a_doc_set_declares() {
+        let dir = TempDir::new().unwrap();
+        let rows = [("Vec", "Struct", "vec.html"), ("VecDeque", "Struct", "vec_deque.html"), ("BitVec", "Struct", "bit_vec.html")];
+        fixture(dir.path(), "Rust", &rows).await;
+        fixture(dir.path(), "Std", &rows).await;
+        std::fs::write(dir.path().join("Rust.docset").join("meta.json"), r#"{"name": "Rust", "extra": {"resultLimit": 2}}"#).unwrap();
+        write_meta(&dir.path().join("Std.docset"), "Std");
+
+        let dash = dash(load(dir.path()).await.unwrap().doc_sets);
+        let options = SearchOptions { limit: 30, ..Default::default() };
+        assert_eq!(dash.search("Rust.docset", "vec", &options).await.unwrap().len(), 2);
+        assert_eq!(dash.search("Std.docset", "vec", &options).await.unwrap().len(), 3);
+        // The smaller of both limits applies
+        let options = SearchOptions { limit: 1, ..Default::default() };
+        assert_eq!(dash.search("Rust.docset", "vec", &options).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn checks_report_what_is_broken_per_doc_set() {
+        let dir = TempDir::new().unwrap();
+        fixture(dir.path(), "Rust", &[("Vec", "Struct", "vec.html")]).await;
+        fixture_with(dir.path(), "Broken", &["CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT)".to_string()]).await;
+        for name in ["Rust", "Broken"] {
+            let path = dir.path().join(format!("{name}.docset"));
+            let resources = path.join("Contents").join("Resources");
+            std::fs::create_dir_all(resources.join("Documents")).unwrap();
+            std::fs::rename(path.join("docSet.dsidx"), resources.join("docSet.dsidx")).unwrap();
+            write_meta(&path, name);
+        }
+        std::fs::write(dir.path().join("Rust.docset").join("icon.png"), b"").unwrap();
+
+        let dash = dash(load(dir.path()).await.unwrap().doc_sets);
+        let documents = dir.path().join("Broken.docset").join("Contents").join("Resources").join("Documents");
+        std::fs::remove_dir(&documents).unwrap();
+        let mut checks = dash.check().await.unwrap();
+        checks.sort_by(|a, b| a.id.cmp(&b.id));
+        let failed: Vec<(&str, bool, Vec<&str>)> = checks
+            .iter()
+            .map(|ds| (ds.id.as_str(), ds.healthy, ds.checks.iter().filter(|c| c.error.is_some()).map(|c| c.name).collect()))
+            .collect();
+        assert_eq!(failed, vec![
+            ("Broken.docset", false, vec!["schema", "documents", "icon"]),
+            ("Rust.docset", true, vec![]),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn finds_indexes_named_otherwise() {
+        let dir = TempDir::new().unwrap();
+        fixture(dir.path(), "Rust", &[("Vec", "Struct", "vec.html")]).await;
+        let path = dir.path().join("Rust.docset");
+        let resources = path.join("contents").join("resources");
+        std::fs::create_dir_all(resources.join("Documents")).unwrap();
+        std::fs::rename(path.join("docSet.dsidx"), resources.join("rust.DSIDX")).unwrap();
+        write_meta(&path, "Rust");
+
+        let loaded = load(dir.path()).await.unwrap();
+        assert_eq!(loaded.doc_sets[0].resource_root, resources.join("Documents"));
+        let dash = dash(loaded.doc_sets);
+        assert_eq!(dash.search("Rust.docset", "Vec", &Default::default()).await.unwrap()[0].title.as_ref(), "Vec");
+    }
+
+    #[tokio::test]
+    async fn a_warm_metadata_cache_skips_meta_json() {
+        let dir = TempDir::new().unwrap();
+        fixture(dir.path(), "Rust", &[]).await;
+        let path = dir.path().join("Rust.docset");
+        write_meta(&path, "Rust");
+        let cache_file = dir.path().join("cache.json");
+        let config = DashConfig::default();
+        let name = |cache: MetaCache| async {
+            let mut cache = cache;
+            let doc_set = DashDocSet::new(&path, &mut cache, &config).await.unwrap();
+            cache.save().await.unwrap();
+            doc_set.name.to_string()
+        };
+        assert_eq!(name(MetaCache::load(cache_file.clone()).await).await, "Rust");
+
+        // Rewritten in place with its time kept, only the cache still knows the old name
+        let meta = path.join("meta.json");
+        let modified = std::fs::metadata(&meta).unwrap().modified().unwrap();
+        write_meta(&path, "Changed");
+        std::fs::File::options().write(true).open(&meta).unwrap().set_modified(modified).unwrap();
+        assert_eq!(name(MetaCache::load(cache_file.clone()).await).await, "Rust");
+
+        std::fs::File::options().write(true).open(&meta).unwrap().set_modified(SystemTime::now()).unwrap();
+        assert_eq!(name(MetaCache::load(cache_file.clone()).await).await, "Changed");
+    }
+
+    #[tokio::test]
+    async fn retries_searches_while_the_index_is_locked() {
+        let dir = TempDir::new().unwrap();
+        let doc_set = fixture(dir.path(), "Rust", &[("Vec", "Struct", "vec.html")]).await;
+        let index = dir.path().join("Rust.docset").join("docSet.dsidx");
+        let dash = dash(vec![doc_set]);
+
+        // An exclusive transaction keeps readers out, the way a writing Zeal would
+        let mut writer = SqliteConnectOptions::new().filename(&index).connect().await.unwrap();
+        sqlx::query("BEGIN EXCLUSIVE").execute(&mut writer).await.unwrap();
+        let unlock = tokio::spawn(async move {
+            sleep(Duration::from_millis(40)).await;
+            sqlx::query("COMMIT").execute(&mut writer).await.unwrap();
+            writer
+        });
+        assert_eq!(search(&dash, "Vec", &SearchOptions::default()).await, vec![("Vec".to_string(), 100)]);
+
+        // Never released, the doc set comes back empty rather than failing the query
+        let mut writer = unlock.await.unwrap();
+        sqlx::query("BEGIN EXCLUSIVE").execute(&mut writer).await.unwrap();
+        assert_eq!(search(&dash, "Vec", &SearchOptions::default()).await, vec![]);
+    }
+
+    #[tokio::test]
+    async fn searches_after_clean_up() {
+        let dir = TempDir::new().unwrap();
+        let dash = dash(vec![fixture(dir.path(), "Rust", &[("Vec", "Struct", "vec.html")]).await]);
+
+        dash.clean_up().await.unwrap();
+        assert_eq!(search(&dash, "Vec", &Default::default()).await, vec![("Vec".to_string(), 100)]);
+    }
+
+    #[tokio::test]
+    async fn leaves_hidden_types_out() {
+        let dir = TempDir::new().unwrap();
+        let dash = dash(vec![fixture(dir.path(), "Rust", &[
+            ("Vec", "Struct", "std/vec/struct.Vec.html"),
+            ("Vec", "Guide", "book/vectors.html"),
+        ]).await]);
+
+        let options = SearchOptions {
+            hidden_types: vec![EntryType::Guide],
+            ..Default::default()
+        };
+        let entries = dash.search("Rust", "Vec", &options).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].entry_type, EntryType::Struct);
+    }
+
+    #[test]
+    fn only_accented_letters_are_wildcards_of_a_strict_pattern() {
+        assert_eq!(diacritic_pattern("café", false), "%caf_%");
+        assert_eq!(diacritic_pattern("cafe\u{301}", false), "%caf_%");
+        assert_eq!(diacritic_pattern("cafe", false), "%cafe%");
+        assert_eq!(diacritic_pattern("cafe", true), "%__f_%");
+        assert_eq!(diacritic_pattern("html", true), "%html%");
+        assert_eq!(diacritic_pattern("10%_", true), r"%10\%\_%");
+    }
+
+    #[tokio::test]
+    async fn folds_diacritics_both_ways() {
+        let dir = TempDir::new().unwrap();
+        let dash = dash(vec![fixture(dir.path(), "Rust", &[
+            ("café", "Function", "cafe-accent.html"),
+            ("cafe", "Function", "cafe.html"),
+            ("cofe", "Function", "cofe.html"),
+        ]).await]);
+        let options = SearchOptions { fold_diacritics: true, ..Default::default() };
+
+        for q in ["cafe", "café"] {
+            let mut titles: Vec<String> = search(&dash, q, &options).await.into_iter().map(|(title, _)| title).collect();
+            titles.sort();
+            assert_eq!(titles, vec!["cafe", "café"], "searching {q}");
+        }
+    }
+
+    #[tokio::test]
+    async fn aliases_open_their_target_page() {
+        let dir = TempDir::new().unwrap();
+        let statements = [
+            "CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT)",
+            "CREATE TABLE aliases(alias TEXT, target TEXT)",
+            "INSERT INTO searchIndex(name, type, path) VALUES ('HTMLCollection', 'Interface', 'dom/htmlcollection.html')",
+            "INSERT INTO aliases VALUES ('NodeList', 'HTMLCollection')",
+        ].map(String::from);
+        let dash = dash(vec![fixture_with(dir.path(), "Rust", &statements).await]);
+
+        let rs = dash.search("Rust", "NodeList", &SearchOptions::default()).await.unwrap();
+        let rs: Vec<(&str, &str, &str, usize)> = rs.iter().map(|e| (e.title.as_ref(), e.desc.as_ref(), e.id.as_ref(), e.relevance)).collect();
+        assert_eq!(rs, vec![("NodeList", "(alias of HTMLCollection)", "dom/htmlcollection.html", 100 - ALIAS_RELEVANCE_PENALTY)]);
+
+        // Doc sets without the table search as usual
+        let plain = fixture(dir.path(), "Plain", &[("HTMLCollection", "Interface", "dom/htmlcollection.html")]).await;
+        assert!(!plain.has_aliases);
+    }
+
+    #[tokio::test]
+    async fn folds_diacritics_of_aliases_both_ways() {
+        let dir = TempDir::new().unwrap();
+        let statements = [
+            "CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT)",
+            "CREATE TABLE aliases(alias TEXT, target TEXT)",
+            "INSERT INTO searchIndex(name, type, path) VALUES ('Coffee', 'Class', 'coffee.html'), ('Tea', 'Class', 'tea.html')",
+            "INSERT INTO aliases VALUES ('café', 'Coffee'), ('cha', 'Tea')",
+        ].map(String::from);
+        let dash = dash(vec![fixture_with(dir.path(), "Rust", &statements).await]);
+        let options = SearchOptions { fold_diacritics: true, ..Default::default() };
+
+        for q in ["cafe", "café"] {
+            let titles: Vec<String> = search(&dash, q, &options).await.into_iter().map(|(title, _)| title).collect();
+            assert_eq!(titles, vec!["café"], "searching {q}");
+        }
+    }
+
+    #[tokio::test]
+    async fn searches_core_data_indexes() {
+        let dir = TempDir::new().unwrap();
+        let statements = [
+            "CREATE TABLE ZTOKENTYPE(Z_PK INTEGER PRIMARY KEY, ZTYPENAME TEXT)",
+            "CREATE TABLE ZFILEPATH(Z_PK INTEGER PRIMARY KEY, ZPATH TEXT)",
+            "CREATE TABLE ZTOKENMETAINFORMATION(Z_PK INTEGER PRIMARY KEY, ZANCHOR TEXT, ZABSTRACT TEXT, ZDECLARATION TEXT, ZFILE INTEGER)",
+            "CREATE TABLE ZTOKEN(Z_PK INTEGER PRIMARY KEY, ZTOKENNAME TEXT, ZTOKENTYPE INTEGER, ZMETAINFORMATION INTEGER)",
+            "INSERT INTO ZTOKENTYPE VALUES (1, 'Class'), (2, 'Method')",
+            "INSERT INTO ZFILEPATH VALUES (1, 'uikit/uiview.html')",
+            "INSERT INTO ZTOKENMETAINFORMATION VALUES \
+                (1, 'overview', 'An object that manages content.', 'class UIView', 1), \
+                (2, 'addSubview', '', 'func addSubview(_ view: UIView)', 1)",
+            "INSERT INTO ZTOKEN VALUES (1, 'UIView', 1, 1), (2, 'addSubview', 2, 2)",
+        ].map(String::from);
+        let doc_set = fixture_with(dir.path(), "Rust", &statements).await;
+        assert_eq!(doc_set.schema, IndexSchema::CoreData);
+        let dash = dash(vec![doc_set]);
+
+        let entries = dash.search("Rust", "UIView", &Default::default()).await.unwrap();
+        assert_eq!(entries, vec![SearchEntry {
+            entry_type: EntryType::Class,
+            title: "UIView".into(),
+            desc: "An object that manages content.".into(),
+            id: "uikit/uiview.html#overview".into(),
+            relevance: 100,
+        }]);
+
+        // Entries without an abstract are described by their declaration
+        let entries = dash.search("Rust", "addSubview", &Default::default()).await.unwrap();
+        assert_eq!(entries[0].desc.as_ref(), "func addSubview(_ view: UIView)");
+    }
 }
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::config::DevDocsConfig;
+use crate::opener;
+use crate::provider::{name_relevance, sort_by_keyword, DocProvider, DocSet, EntryType, SearchEntry, SearchOptions};
+
+const DOCUMENTS_URL: &str = "https://documents.devdocs.io";
+
+#[derive(Debug, Deserialize)]
+struct DevDocsIndex {
+    entries: Vec<DevDocsEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DevDocsEntry {
+    name: Arc<str>,
+    path: Arc<str>,
+    #[serde(rename = "type")]
+    entry_type: Arc<str>,
+}
+
+/// State of a doc's index downloaded from DevDocs.
+enum RemoteIndex {
+    Loading,
+    Loaded(Arc<Vec<DevDocsEntry>>),
+}
+
+/// Looks symbols up in DevDocs' online documentation, one doc set per configured doc slug.
+///
+/// Indexes are downloaded in the background the first time a doc is searched by its keyword:
+/// that query comes back empty and the next keystroke searches the downloaded index. Global
+/// searches only go through indexes downloaded already. Failed downloads are retried on the
+/// next search, so being offline only ever yields empty results.
+pub struct DevDocs {
+    /// Doc slugs (e.g. `rust`, `python~3.11`) by keyword.
+    docs: HashMap<String, String>,
+    client: reqwest::Client,
+    indexes: Arc<Mutex<HashMap<String, RemoteIndex>>>,
+}
+
+impl DevDocs {
+    pub fn new(config: &DevDocsConfig) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .context("Creating HTTP client")?;
+
+        Ok(Self {
+            docs: config.docs.iter().map(|(kw, slug)| (kw.to_lowercase(), slug.clone())).collect(),
+            client,
+            indexes: Default::default(),
+        })
+    }
+
+    fn to_doc_set(keyword: &str, slug: &str) -> DocSet {
+        DocSet {
+            id: slug.into(),
+            keywords: vec![keyword.into()],
+            name: format!("{slug} (DevDocs)").into(),
+            description: format!("DevDocs documentation for {slug}").into(),
+            icon: Arc::from("internet-web-browser"),
+            homepage: None,
+        }
+    }
+
+    /// The downloaded index of `slug`, starting its download when it isn't there yet and
+    /// `download` is set.
+    fn index(&self, slug: &str, download: bool) -> Option<Arc<Vec<DevDocsEntry>>> {
+        let mut indexes = self.indexes.lock().unwrap();
+        match indexes.get(slug) {
+            Some(RemoteIndex::Loaded(entries)) => return Some(entries.clone()),
+            Some(RemoteIndex::Loading) => return None,
+            None if !download => return None,
+            None => {}
+        }
+
+        indexes.insert(slug.to_string(), RemoteIndex::Loading);
+        let client = self.client.clone();
+        let indexes = self.indexes.clone();
+        let slug = slug.to_string();
+        // Not tied to the query, a download outliving the search deadline still lands in the cache
+        tokio::spawn(async move {
+            match fetch_index(&client, &slug).await {
+                Ok(entries) => {
+                    log::debug!("Downloaded DevDocs index of {slug} with {} entries", entries.len());
+                    indexes.lock().unwrap().insert(slug, RemoteIndex::Loaded(Arc::new(entries)));
+                }
+                Err(e) => {
+                    log::warn!("Unable to download DevDocs index of {slug}: {e:?}");
+                    indexes.lock().unwrap().remove(&slug);
+                }
+            }
+        });
+        None
+    }
+}
+
+async fn fetch_index(client: &reqwest::Client, slug: &str) -> anyhow::Result<Vec<DevDocsEntry>> {
+    let index: DevDocsIndex = client.get(format!("{DOCUMENTS_URL}/{slug}/index.json"))
+        .send().await.context("Requesting index")?
+        .error_for_status()?
+        .json().await.context("Parsing index")?;
+    Ok(index.entries)
+}
+
+/// The page of an index entry path, e.g. `std/vec/struct.vec#method.push`.
+fn page_url(slug: &str, entry_url: &str) -> String {
+    match entry_url.split_once('#') {
+        Some((page, fragment)) => format!("{DOCUMENTS_URL}/{slug}/{page}.html#{fragment}"),
+        None => format!("{DOCUMENTS_URL}/{slug}/{entry_url}.html"),
+    }
+}
+
+#[async_trait]
+impl DocProvider for DevDocs {
+    fn name(&self) -> &str {
+        "DevDocs"
+    }
+
+    fn icon(&self) -> &str {
+        "internet-web-browser"
+    }
+
+    async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>> {
+        Ok(self.docs.iter().map(|(kw, slug)| Self::to_doc_set(kw, slug)).collect())
+    }
+
+    async fn search_doc_sets(&self, keyword: &str) -> anyhow::Result<Vec<DocSet>> {
+        let keyword = keyword.trim().to_lowercase();
+        if keyword.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut rs: Vec<DocSet> = self.docs
+            .iter()
+            .filter(|(kw, _)| kw.starts_with(&keyword))
+            .map(|(kw, slug)| Self::to_doc_set(kw, slug))
+            .collect();
+        sort_by_keyword(&mut rs, &keyword);
+        Ok(rs)
+    }
+
+    fn supports_keyword(&self, keyword: &str) -> bool {
+        let keyword = keyword.trim().to_lowercase();
+        self.docs.keys().any(|kw| kw.starts_with(&keyword))
+    }
+
+    async fn search(&self, doc_set_id: &str, q: &str, options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>> {
+        let entries = match self.index(doc_set_id, !options.global) {
+            Some(v) => v,
+            None => return Ok(vec![]),
+        };
+
+        let q = options.fold(q.trim());
+        let mut rs: Vec<SearchEntry> = entries
+            .iter()
+            .filter(|e| !options.hides(&e.entry_type))
+            .filter_map(|e| {
+                let relevance = name_relevance(&options.fold(&e.name), &q)?;
+                Some(SearchEntry {
+                    entry_type: EntryType::Other(e.entry_type.clone()),
+                    title: e.name.clone(),
+                    desc: e.entry_type.clone(),
+                    id: e.path.clone(),
+                    relevance,
+                })
+            })
+            .collect();
+
+        rs.sort_by(|a, b| b.relevance.cmp(&a.relevance).then(a.title.len().cmp(&b.title.len())));
+        rs.truncate(options.limit);
+        Ok(rs)
+    }
+
+    async fn open(&self, doc_set_id: &str, entry_url: &str) -> anyhow::Result<()> {
+        opener::open(&page_url(doc_set_id, entry_url))
+    }
+
+    async fn resolve_url(&self, doc_set_id: &str, entry_url: &str) -> Option<String> {
+        Some(page_url(doc_set_id, entry_url))
+    }
+
+    async fn open_doc_set(&self, doc_set_id: &str) -> anyhow::Result<()> {
+        opener::open(&format!("{DOCUMENTS_URL}/{doc_set_id}/index.html"))
+    }
+
+    async fn clean_up(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn global_searches_dont_download_indexes() {
+        let config = DevDocsConfig {
+            docs: [("rs".to_string(), "rust".to_string())].into(),
+            ..Default::default()
+        };
+        let devdocs = DevDocs::new(&config).unwrap();
+        let options = SearchOptions { global: true, ..Default::default() };
+
+        assert!(devdocs.search("rust", "vec", &options).await.unwrap().is_empty());
+        assert!(devdocs.indexes.lock().unwrap().is_empty());
+    }
+}
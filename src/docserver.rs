@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Characters escaped in the path of a served URL, `/` separates the folders.
+const PATH_ESCAPES: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>').add(b'?').add(b'`').add(b'{').add(b'}');
+
+/// Content types by lowercase file extension, anything else is served as bytes.
+const CONTENT_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("json", "application/json"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+];
+
+/// Document folders served, by doc set id.
+type Roots = Arc<Mutex<HashMap<String, PathBuf>>>;
+
+/// Serves doc set pages over HTTP on localhost, so relative stylesheets and scripts load the
+/// way they do on the web rather than through `file://`. Each doc set is served under its
+/// id, `http://127.0.0.1:<port>/<doc set id>/<path>`.
+///
+/// The server starts with the first page asked for and keeps its port until it's dropped, so
+/// pages already open in a browser keep loading their assets.
+#[derive(Default)]
+pub struct DocServer {
+    roots: Roots,
+    running: tokio::sync::Mutex<Option<Running>>,
+}
+
+struct Running {
+    port: u16,
+    task: JoinHandle<()>,
+}
+
+impl DocServer {
+    /// The URL of `file`, relative to the documents `root` of the doc set `id`.
+    pub async fn url(&self, id: &str, root: &Path, file: &Path) -> anyhow::Result<String> {
+        self.roots.lock().unwrap().insert(id.to_string(), root.to_path_buf());
+        let port = self.port().await?;
+        Ok(format!(
+            "http://127.0.0.1:{port}/{}/{}",
+            utf8_percent_encode(id, NON_ALPHANUMERIC),
+            utf8_percent_encode(&file.to_string_lossy(), PATH_ESCAPES),
+        ))
+    }
+
+    async fn port(&self) -> anyhow::Result<u16> {
+        let mut running = self.running.lock().await;
+        if let Some(running) = running.as_ref() {
+            return Ok(running.port);
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.context("Binding the doc server")?;
+        let port = listener.local_addr()?.port();
+        log::info!("Serving doc sets on http://127.0.0.1:{port}");
+        *running = Some(Running {
+            port,
+            task: tokio::spawn(serve(listener, self.roots.clone())),
+        });
+        Ok(port)
+    }
+
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        log::debug!("Stopping the doc server on port {}", self.port);
+        self.task.abort();
+    }
+}
+
+async fn serve(listener: TcpListener, roots: Roots) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                log::warn!("Doc server unable to accept a connection: {e}");
+                continue;
+            }
+        };
+        let roots = roots.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &roots).await {
+                log::debug!("Doc server request failed: {e:?}");
+            }
+        });
+    }
+}
+
+/// Answers a single `GET`, then closes the connection.
+async fn respond(mut stream: TcpStream, roots: &Roots) -> anyhow::Result<()> {
+    let (read, write) = stream.split();
+    let target = read_get_target(read).await?;
+    let file = target.as_deref().and_then(|target| resolve(&roots.lock().unwrap(), target));
+    let (status, content_type, body) = match file {
+        Some(file) => match tokio::fs::read(&file).await {
+            Ok(body) => ("200 OK", content_type(&file), body),
+            Err(_) => ("404 Not Found", "text/plain", b"Not found".to_vec()),
+        },
+        None if target.is_none() => ("405 Method Not Allowed", "text/plain", b"Only GET is supported".to_vec()),
+        None => ("404 Not Found", "text/plain", b"Not found".to_vec()),
+    };
+    write_response(write, status, content_type, &body).await
+}
+
+/// Reads a request up to the end of its headers, returning its target when it's a `GET`.
+pub async fn read_get_target(read: impl AsyncRead + Unpin) -> anyhow::Result<Option<String>> {
+    let mut reader = BufReader::new(read);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    // Headers don't change anything we serve, they're read to get past them
+    let mut header = String::new();
+    while reader.read_line(&mut header).await? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    Ok(match request_line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["GET", target, _] => Some(target.to_string()),
+        _ => None,
+    })
+}
+
+/// Writes a complete response and closes the connection.
+pub async fn write_response(mut write: impl AsyncWrite + Unpin, status: &str, content_type: &str, body: &[u8]) -> anyhow::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len(),
+    );
+    write.write_all(head.as_bytes()).await?;
+    write.write_all(body).await?;
+    write.shutdown().await?;
+    Ok(())
+}
+
+/// The file a request target points to. Paths leaving the doc set's documents are refused.
+fn resolve(roots: &HashMap<String, PathBuf>, target: &str) -> Option<PathBuf> {
+    let path = target.split(['?', '#']).next()?;
+    let (id, file) = path.trim_start_matches('/').split_once('/')?;
+    let root = roots.get(percent_decode_str(id).decode_utf8().ok()?.as_ref())?;
+    let file = percent_decode_str(file).decode_utf8().ok()?;
+    let file = Path::new(file.as_ref());
+    match file.components().all(|c| matches!(c, Component::Normal(_))) {
+        true => Some(root.join(file)),
+        false => None,
+    }
+}
+
+fn content_type(file: &Path) -> &'static str {
+    let ext = file.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    CONTENT_TYPES
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map_or("application/octet-stream", |(_, t)| t)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    async fn get(url: &str) -> String {
+        let (host, target) = url.trim_start_matches("http://").split_once('/').unwrap();
+        let mut stream = TcpStream::connect(host).await.unwrap();
+        stream.write_all(format!("GET /{target} HTTP/1.1\r\nHost: {host}\r\n\r\n").as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_doc_set_files_and_nothing_outside() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("Documents");
+        std::fs::create_dir_all(root.join("std")).unwrap();
+        std::fs::write(root.join("std").join("vec page.html"), "<h1>Vec</h1>").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+
+        let server = DocServer::default();
+        let url = server.url("Rust", &root, Path::new("std/vec page.html")).await.unwrap();
+        assert!(url.starts_with("http://127.0.0.1:") && url.ends_with("/Rust/std/vec%20page.html"), "{url}");
+        let response = get(&format!("{url}#method.push")).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains("Content-Type: text/html; charset=utf-8\r\n"));
+        assert!(response.ends_with("\r\n\r\n<h1>Vec</h1>"));
+
+        let outside = url.replace("std/vec%20page.html", "../secret.txt");
+        assert!(get(&outside).await.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
@@ -0,0 +1,44 @@
+use thiserror::Error;
+use zbus::fdo;
+
+/// Failures reported over D-Bus. Providers keep returning `anyhow` errors, they're sorted into
+/// these at the boundary so callers get a plain message under a fitting D-Bus error name rather
+/// than a debug dump.
+#[derive(Debug, Error)]
+pub enum DocError {
+    #[error("Unknown provider {0}")]
+    ProviderNotFound(String),
+    #[error("Unknown doc set {doc_set} of {provider}")]
+    DocSetNotFound { provider: String, doc_set: String },
+    #[error("Unable to open {target}: {source:#}")]
+    OpenFailed { target: String, source: anyhow::Error },
+    #[error("Doc set database error: {0}")]
+    Db(#[from] sqlx::Error),
+    #[error("Unable to parse the {what}: {source}")]
+    Parse { what: &'static str, source: serde_json::Error },
+    #[error("{0:#}")]
+    Failed(anyhow::Error),
+}
+
+impl From<anyhow::Error> for DocError {
+    /// Database errors keep their variant however much context they were wrapped in.
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<sqlx::Error>() {
+            Ok(e) => Self::Db(e),
+            Err(e) => Self::Failed(e),
+        }
+    }
+}
+
+impl From<DocError> for fdo::Error {
+    fn from(e: DocError) -> Self {
+        let message = e.to_string();
+        match e {
+            DocError::ProviderNotFound(_) | DocError::DocSetNotFound { .. } | DocError::Parse { .. } => {
+                fdo::Error::InvalidArgs(message)
+            }
+            DocError::Db(_) => fdo::Error::IOError(message),
+            DocError::OpenFailed { .. } | DocError::Failed(_) => fdo::Error::Failed(message),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use tokio::task::spawn_blocking;
+
+const DOCSET_LIST_URL: &str = "https://api.zealdocs.org/v1/docsets";
+const FEED_URL: &str = "https://kapeli.com/feeds";
+
+/// A doc set that can be installed from Kapeli's feeds.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AvailableDocSet {
+    pub name: String,
+    pub title: String,
+    #[serde(default)]
+    pub versions: Vec<String>,
+}
+
+/// Downloads doc sets from Kapeli's feeds into the doc set root.
+pub struct Feeds {
+    root: PathBuf,
+    client: reqwest::Client,
+}
+
+impl Feeds {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn list_available(&self) -> anyhow::Result<Vec<AvailableDocSet>> {
+        self.client.get(DOCSET_LIST_URL)
+            .send().await.context("Requesting doc set list")?
+            .error_for_status()?
+            .json().await.context("Parsing doc set list")
+    }
+
+    /// Downloads and unpacks the doc set `name`, returning the installed folder.
+    pub async fn install(&self, name: &str) -> anyhow::Result<PathBuf> {
+        anyhow::ensure!(
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+            "Invalid doc set name {name}"
+        );
+
+        let available = self.list_available().await?;
+        let doc_set = available
+            .into_iter()
+            .find(|ds| ds.name == name)
+            .with_context(|| format!("Doc set {name} is not available"))?;
+
+        let target = self.root.join(format!("{name}.docset"));
+        anyhow::ensure!(!target.exists(), "{} is already installed", target.display());
+
+        let feed = self.client.get(format!("{FEED_URL}/{name}.xml"))
+            .send().await.context("Requesting feed")?
+            .error_for_status()?
+            .text().await?;
+        let url = xml_element(&feed, "url").context("Feed has no download url")?;
+        let version = xml_element(&feed, "version").unwrap_or_default();
+
+        log::info!("Downloading doc set {name} {version} from {url}");
+        let archive = self.client.get(url)
+            .send().await.context("Downloading doc set")?
+            .error_for_status()?
+            .bytes().await?;
+
+        let meta = serde_json::json!({
+            "name": doc_set.name,
+            "title": doc_set.title,
+            "version": version,
+        });
+        let staging = self.root.join(format!(".{name}.download"));
+        spawn_blocking(move || {
+            let rs = unpack_doc_set(&archive, &staging, &target, &meta);
+            let _ = std::fs::remove_dir_all(&staging);
+            rs.map(|_| target)
+        }).await?
+    }
+}
+
+/// Unpacks the archive into `staging`, checks it holds exactly one usable doc set, then moves
+/// it to `target`. Nothing is written to `target` unless the whole archive decoded cleanly.
+fn unpack_doc_set(archive: &[u8], staging: &Path, target: &Path, meta: &serde_json::Value) -> anyhow::Result<()> {
+    let _ = std::fs::remove_dir_all(staging);
+    std::fs::create_dir_all(staging).context("Creating staging folder")?;
+    tar::Archive::new(GzDecoder::new(Cursor::new(archive)))
+        .unpack(staging)
+        .context("Unpacking doc set archive")?;
+
+    let mut doc_sets = std::fs::read_dir(staging)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "docset"));
+    let unpacked = doc_sets.next().context("Archive contains no doc set")?;
+    anyhow::ensure!(doc_sets.next().is_none(), "Archive contains more than one doc set");
+    anyhow::ensure!(
+        unpacked.join("Contents").join("Resources").join("docSet.dsidx").is_file(),
+        "Doc set in archive has no index"
+    );
+
+    std::fs::write(unpacked.join("meta.json"), serde_json::to_vec_pretty(meta)?).context("Writing meta.json")?;
+    std::fs::rename(&unpacked, target).context("Moving doc set into place")?;
+    Ok(())
+}
+
+/// Returns the text of the first `<name>` element, good enough for Kapeli's flat feed files.
+fn xml_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{name}>");
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&format!("</{name}>"))?;
+    Some(xml[start..end].trim())
+}
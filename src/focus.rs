@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use zbus::{dbus_interface, Connection, Proxy};
+
+/// Tells which application had focus before KRunner was opened, for searching its doc set
+/// with `SearchConfig::focused_prefix`.
+#[async_trait]
+pub trait FocusSource {
+    /// The window class of that application, `None` when it isn't known.
+    async fn window_class(&self) -> Option<String>;
+}
+
+pub type DynFocusSource = Arc<dyn FocusSource + Send + Sync + 'static>;
+
+/// The keyword `window_keywords` maps `class` to. Classes are compared case-insensitively and
+/// a reverse-DNS class (`org.kde.kate`) also matches by its last part (`kate`).
+pub fn keyword_for_class<'a>(window_keywords: &'a HashMap<String, String>, class: &str) -> Option<&'a str> {
+    let short = class.rsplit('.').next().unwrap_or(class);
+    [class, short].into_iter().find_map(|class| {
+        window_keywords
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(class))
+            .map(|(_, keyword)| keyword.as_str())
+    })
+}
+
+/// Windows taking focus when KRunner opens, never the application the user came from.
+const LAUNCHER_CLASSES: &[&str] = &["krunner", "org.kde.krunner", "plasmashell", "org.kde.plasmashell"];
+
+const SCRIPT_NAME: &str = "kdashdoc-focus";
+
+/// Reports every window activation to [KWinFocus::window_activated], KWin 6 names first.
+const SCRIPT: &str = r#"
+function report(window) {
+    if (window) {
+        callDBus("dev.fanchao.DashDoc", "{object_path}", "dev.fanchao.DashDoc.Focus", "WindowActivated", String(window.resourceClass));
+    }
+}
+(workspace.windowActivated || workspace.clientActivated).connect(report);
+report(workspace.activeWindow || workspace.activeClient);
+"#;
+
+/// Follows window activations through a KWin script, remembering the last window that isn't
+/// KRunner itself. KWin has no call returning the active window, and by the time a query
+/// arrives KRunner has focus anyway.
+#[derive(Clone, Default)]
+pub struct KWinFocus {
+    last: Arc<Mutex<Option<String>>>,
+}
+
+impl KWinFocus {
+    /// Loads the reporting script into KWin, which calls back on `object_path` of our bus name.
+    pub async fn install(&self, connection: &Connection, object_path: &str) -> anyhow::Result<()> {
+        // Only we can write to the runtime dir, unlike the shared temp dir
+        let script = dirs::runtime_dir()
+            .context("No XDG_RUNTIME_DIR to write the KWin script to")?
+            .join(format!("{SCRIPT_NAME}.js"));
+        tokio::fs::write(&script, SCRIPT.replace("{object_path}", object_path)).await?;
+        let scripting = Proxy::new(connection, "org.kde.KWin", "/Scripting", "org.kde.kwin.Scripting").await?;
+        // A script left over from an earlier run would report to a name we may no longer own
+        let _: bool = scripting.call("unloadScript", &(SCRIPT_NAME,)).await?;
+        let _: i32 = scripting.call("loadScript", &(script.to_string_lossy().as_ref(), SCRIPT_NAME)).await?;
+        let _: () = scripting.call("start", &()).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FocusSource for KWinFocus {
+    async fn window_class(&self) -> Option<String> {
+        self.last.lock().unwrap().clone()
+    }
+}
+
+#[dbus_interface(name = "dev.fanchao.DashDoc.Focus")]
+impl KWinFocus {
+    /// Called by the KWin script whenever a window gets focus.
+    fn window_activated(&self, class: String) {
+        if class.is_empty() || LAUNCHER_CLASSES.iter().any(|c| c.eq_ignore_ascii_case(&class)) {
+            return;
+        }
+        *self.last.lock().unwrap() = Some(class);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_window_classes_to_keywords() {
+        let window_keywords: HashMap<String, String> = [("kate", "rust"), ("jetbrains-pycharm", "python")]
+            .into_iter()
+            .map(|(class, keyword)| (class.to_string(), keyword.to_string()))
+            .collect();
+
+        assert_eq!(keyword_for_class(&window_keywords, "kate"), Some("rust"));
+        assert_eq!(keyword_for_class(&window_keywords, "org.kde.Kate"), Some("rust"));
+        assert_eq!(keyword_for_class(&window_keywords, "Jetbrains-PyCharm"), Some("python"));
+        assert_eq!(keyword_for_class(&window_keywords, "firefox"), None);
+        assert_eq!(keyword_for_class(&HashMap::new(), "kate"), None);
+    }
+
+    #[tokio::test]
+    async fn remembers_the_last_window_that_isnt_krunner() {
+        let focus = KWinFocus::default();
+        assert_eq!(focus.window_class().await, None);
+
+        focus.window_activated("org.kde.kate".into());
+        focus.window_activated("org.kde.krunner".into());
+        focus.window_activated("plasmashell".into());
+        assert_eq!(focus.window_class().await.as_deref(), Some("org.kde.kate"));
+    }
+}
@@ -0,0 +1,322 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use tokio::process::Command;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+use crate::config::GoDocConfig;
+use crate::logging::debug_throttled;
+use crate::opener;
+use crate::provider::{name_relevance, DocProvider, DocSet, EntryType, SearchEntry, SearchOptions};
+
+const DOC_SET_ID: &str = "godoc";
+
+struct GoDocEntry {
+    /// `bytes.Buffer.Len`
+    title: Arc<str>,
+    entry_type: EntryType,
+    /// The declaration line, e.g. `func (b *Buffer) Len() int`.
+    signature: Arc<str>,
+    /// `bytes#Buffer.Len`, the package and the anchor of its page.
+    id: Arc<str>,
+}
+
+/// Serves the exported symbols of the configured Go packages as a single doc set, indexed from
+/// `go doc -all` when the provider starts. Entries open on pkg.go.dev or a local pkgsite.
+pub struct GoDoc {
+    keyword: String,
+    base_url: String,
+    entries: Vec<GoDocEntry>,
+}
+
+impl GoDoc {
+    /// Indexes every configured package, skipping the ones `go doc` fails on or takes longer
+    /// than `timeout_ms` for. Without a Go toolchain the doc set is empty.
+    pub async fn new(config: &GoDocConfig) -> Self {
+        let mut join_set = JoinSet::new();
+        for package in &config.packages {
+            let config = config.clone();
+            let package = package.clone();
+            join_set.spawn(async move {
+                let output = go_doc(&config, &package).await;
+                (package, output)
+            });
+        }
+
+        let mut entries = vec![];
+        while let Some(result) = join_set.join_next().await {
+            match result {
+                Ok((package, Ok(output))) => entries.extend(parse_go_doc(&package, &output)),
+                Ok((package, Err(e))) => log::warn!("Skipping Go package {package}: {e:?}"),
+                Err(e) => log::error!("Indexing a Go package panicked: {e:?}"),
+            }
+        }
+        entries.sort_by(|a, b| a.title.cmp(&b.title));
+        log::debug!("Found {} Go doc entries", entries.len());
+
+        Self {
+            keyword: config.keyword.to_lowercase(),
+            base_url: config.base_url.trim_end_matches('/').to_string(),
+            entries,
+        }
+    }
+
+    fn to_doc_set(&self) -> DocSet {
+        DocSet {
+            id: DOC_SET_ID.into(),
+            keywords: vec![self.keyword.as_str().into()],
+            name: Arc::from("Go"),
+            description: Arc::from("Go package documentation"),
+            icon: Arc::from("text-x-go"),
+            homepage: None,
+        }
+    }
+
+    fn page_url(&self, entry_url: &str) -> String {
+        format!("{}/{entry_url}", self.base_url)
+    }
+}
+
+/// Runs `go doc -all` for `package`, killing it once it takes longer than the config allows.
+async fn go_doc(config: &GoDocConfig, package: &str) -> anyhow::Result<String> {
+    let output = Command::new(&config.go)
+        .args(["doc", "-all", package])
+        .kill_on_drop(true)
+        .output();
+    let output = timeout(Duration::from_millis(config.timeout_ms), output)
+        .await
+        .with_context(|| format!("go doc took longer than {}ms", config.timeout_ms))?
+        .with_context(|| format!("Running {}", config.go.display()))?;
+    anyhow::ensure!(
+        output.status.success(),
+        "go doc failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim(),
+    );
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Picks the declarations out of `go doc -all` output. They're the lines starting at the
+/// first column, plus the names inside `const (` and `var (` groups; everything else is
+/// documentation text, indented.
+fn parse_go_doc(package: &str, output: &str) -> Vec<GoDocEntry> {
+    let mut entries = vec![];
+    let mut group: Option<EntryType> = None;
+    for line in output.lines() {
+        if let Some(entry_type) = &group {
+            if line == ")" {
+                group = None;
+            } else if let Some(name) = line.strip_prefix('\t').and_then(leading_ident) {
+                entries.push(to_entry(package, name, entry_type.clone(), line.trim()));
+            }
+            continue;
+        }
+
+        let (entry_type, symbol) = match parse_declaration(line) {
+            Some(Declaration::Group(entry_type)) => {
+                group = Some(entry_type);
+                continue;
+            }
+            Some(Declaration::Symbol(entry_type, symbol)) => (entry_type, symbol),
+            None => continue,
+        };
+        let signature = line.trim_end_matches('{').trim_end();
+        entries.push(to_entry(package, &symbol, entry_type, signature));
+    }
+    entries
+}
+
+enum Declaration {
+    /// `const (` or `var (`, one name per line follows up to the closing `)`.
+    Group(EntryType),
+    /// The entry type and anchor of a single declaration, e.g. `Buffer.Len` for a method.
+    Symbol(EntryType, String),
+}
+
+fn parse_declaration(line: &str) -> Option<Declaration> {
+    let (keyword, rest) = line.split_once(' ')?;
+    match keyword {
+        "const" | "var" => {
+            let entry_type = match keyword {
+                "const" => EntryType::Constant,
+                _ => EntryType::Variable,
+            };
+            match rest.starts_with('(') {
+                true => Some(Declaration::Group(entry_type)),
+                false => Some(Declaration::Symbol(entry_type, leading_ident(rest)?.to_string())),
+            }
+        }
+        "type" => {
+            let name = leading_ident(rest)?;
+            let definition = rest[name.len()..].trim_start();
+            // Skips generic parameters, `Pointer[T any] struct`
+            let definition = match definition.strip_prefix('[') {
+                Some(generic) => generic.split_once(']').map_or("", |(_, d)| d.trim_start()),
+                None => definition,
+            };
+            let entry_type = match definition {
+                d if d.starts_with("struct") => EntryType::Struct,
+                d if d.starts_with("interface") => EntryType::Interface,
+                _ => EntryType::Type,
+            };
+            Some(Declaration::Symbol(entry_type, name.to_string()))
+        }
+        "func" => match rest.strip_prefix('(') {
+            Some(receiver) => {
+                let (receiver, method) = receiver.split_once(')')?;
+                // `b *Buffer` or `m Map[K, V]`, the anchor only names the type
+                let receiver = receiver.split('[').next()?.split_whitespace().last()?.trim_start_matches('*');
+                let receiver = leading_ident(receiver)?;
+                let method = leading_ident(method.trim_start())?;
+                Some(Declaration::Symbol(EntryType::Method, format!("{receiver}.{method}")))
+            }
+            None => Some(Declaration::Symbol(EntryType::Function, leading_ident(rest)?.to_string())),
+        },
+        _ => None,
+    }
+}
+
+/// The identifier `s` starts with, `None` when it doesn't start with one.
+fn leading_ident(s: &str) -> Option<&str> {
+    let end = s
+        .char_indices()
+        .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+        .map_or(s.len(), |(i, _)| i);
+    let ident = &s[..end];
+    match ident.chars().next() {
+        Some(c) if c.is_alphabetic() || c == '_' => Some(ident),
+        _ => None,
+    }
+}
+
+fn to_entry(package: &str, symbol: &str, entry_type: EntryType, signature: &str) -> GoDocEntry {
+    // The title is qualified by the package name, the last element of its import path
+    let package_name = package.rsplit('/').next().unwrap_or(package);
+    GoDocEntry {
+        title: format!("{package_name}.{symbol}").into(),
+        entry_type,
+        signature: signature.into(),
+        id: format!("{package}#{symbol}").into(),
+    }
+}
+
+#[async_trait]
+impl DocProvider for GoDoc {
+    fn name(&self) -> &str {
+        "godoc"
+    }
+
+    fn icon(&self) -> &str {
+        "text-x-go"
+    }
+
+    async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>> {
+        if self.entries.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![self.to_doc_set()])
+    }
+
+    async fn search_doc_sets(&self, keyword: &str) -> anyhow::Result<Vec<DocSet>> {
+        if !self.supports_keyword(keyword) {
+            return Ok(vec![]);
+        }
+        self.doc_sets().await
+    }
+
+    fn supports_keyword(&self, keyword: &str) -> bool {
+        let keyword = keyword.trim().to_lowercase();
+        !keyword.is_empty() && self.keyword.starts_with(&keyword)
+    }
+
+    async fn search(&self, doc_set_id: &str, q: &str, options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>> {
+        if doc_set_id != DOC_SET_ID {
+            return Ok(vec![]);
+        }
+
+        let q = options.fold(q.trim());
+        let mut rs: Vec<SearchEntry> = self.entries
+            .iter()
+            .filter(|e| !options.hides(e.entry_type.as_str()))
+            .filter_map(|e| {
+                // `println` matches `fmt.Println` as well as `fmt.println` does
+                let symbol = e.id.split_once('#').map_or(e.id.as_ref(), |(_, symbol)| symbol);
+                let relevance = name_relevance(&options.fold(&e.title), &q)
+                    .max(name_relevance(&options.fold(symbol), &q))?;
+                Some(SearchEntry {
+                    entry_type: e.entry_type.clone(),
+                    title: e.title.clone(),
+                    desc: e.signature.clone(),
+                    id: e.id.clone(),
+                    relevance,
+                })
+            })
+            .collect();
+
+        rs.sort_by(|a, b| b.relevance.cmp(&a.relevance).then(a.title.len().cmp(&b.title.len())));
+        rs.truncate(options.limit);
+        debug_throttled!("Searching godoc for {q} got {} results", rs.len());
+        Ok(rs)
+    }
+
+    async fn open(&self, _doc_set_id: &str, entry_url: &str) -> anyhow::Result<()> {
+        opener::open(&self.page_url(entry_url))
+    }
+
+    async fn resolve_url(&self, _doc_set_id: &str, entry_url: &str) -> Option<String> {
+        Some(self.page_url(entry_url))
+    }
+
+    async fn open_doc_set(&self, _doc_set_id: &str) -> anyhow::Result<()> {
+        opener::open(&self.page_url("std"))
+    }
+
+    async fn clean_up(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn declaration(line: &str) -> Option<(EntryType, String)> {
+        match parse_declaration(line)? {
+            Declaration::Symbol(entry_type, symbol) => Some((entry_type, symbol)),
+            Declaration::Group(_) => None,
+        }
+    }
+
+    #[test]
+    fn names_methods_by_their_receiver_type() {
+        assert_eq!(declaration("func (b *Buffer) Len() int"), Some((EntryType::Method, "Buffer.Len".into())));
+        assert_eq!(declaration("func (p *Pointer[T]) Load() *T"), Some((EntryType::Method, "Pointer.Load".into())));
+        assert_eq!(declaration("func (m Map[K, V]) Keys() []K"), Some((EntryType::Method, "Map.Keys".into())));
+        assert_eq!(declaration("func NewBuffer(buf []byte) *Buffer"), Some((EntryType::Function, "NewBuffer".into())));
+    }
+
+    #[test]
+    fn tells_type_kinds_apart_past_generic_parameters() {
+        assert_eq!(declaration("type Pointer[T any] struct {"), Some((EntryType::Struct, "Pointer".into())));
+        assert_eq!(declaration("type Reader interface {"), Some((EntryType::Interface, "Reader".into())));
+        assert_eq!(declaration("type Set[K comparable, V any] map[K]V"), Some((EntryType::Type, "Set".into())));
+        assert_eq!(declaration("    Documentation of Buffer."), None);
+    }
+
+    #[test]
+    fn lists_every_name_of_const_groups() {
+        let output = "package bytes\n\nconst (\n\tMinRead = 512\n\tmaxInt  = int(^uint(0) >> 1)\n)\n\nvar ErrTooLarge = errors.New(\"bytes.Buffer: too large\")\n\nfunc (b *Buffer) Len() int\n    Len returns the number of bytes.\n";
+        let entries: Vec<(String, EntryType, String)> = parse_go_doc("bytes", output)
+            .into_iter()
+            .map(|e| (e.title.to_string(), e.entry_type, e.id.to_string()))
+            .collect();
+        assert_eq!(entries, vec![
+            ("bytes.MinRead".into(), EntryType::Constant, "bytes#MinRead".into()),
+            ("bytes.maxInt".into(), EntryType::Constant, "bytes#maxInt".into()),
+            ("bytes.ErrTooLarge".into(), EntryType::Variable, "bytes#ErrTooLarge".into()),
+            ("bytes.Buffer.Len".into(), EntryType::Method, "bytes#Buffer.Len".into()),
+        ]);
+    }
+}
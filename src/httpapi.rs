@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use percent_encoding::percent_decode_str;
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::docserver::{read_get_target, write_response};
+use crate::krunner::KRunnerPlugin;
+
+/// Serves `GET /search?q=<query>` on `127.0.0.1:port`, answering with the JSON list of
+/// [KRunnerPlugin::search]. The query is typed as it would be in KRunner, keyword first.
+pub async fn start(plugin: KRunnerPlugin, port: u16) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .with_context(|| format!("Binding the search endpoint to port {port}"))?;
+    log::info!("Serving searches on http://127.0.0.1:{port}/search");
+    tokio::spawn(serve(listener, Arc::new(plugin)));
+    Ok(())
+}
+
+async fn serve(listener: TcpListener, plugin: Arc<KRunnerPlugin>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                log::warn!("Search endpoint unable to accept a connection: {e}");
+                continue;
+            }
+        };
+        let plugin = plugin.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &plugin).await {
+                log::debug!("Search request failed: {e:?}");
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream, plugin: &KRunnerPlugin) -> anyhow::Result<()> {
+    let (read, write) = stream.split();
+    let target = read_get_target(read).await?;
+    let query = target.as_deref().map(search_query);
+    let (status, content_type, body) = match query {
+        None => ("405 Method Not Allowed", "text/plain", b"Only GET is supported".to_vec()),
+        Some(None) => ("404 Not Found", "text/plain", b"Try /search?q=<query>".to_vec()),
+        Some(Some(query)) => {
+            let hits = plugin.search(&query).await;
+            ("200 OK", "application/json", serde_json::to_vec(&hits)?)
+        }
+    };
+    write_response(write, status, content_type, &body).await
+}
+
+/// The `q` parameter of a `/search` target, `+` standing for a space as in forms. `None` for
+/// any other path.
+fn search_query(target: &str) -> Option<String> {
+    let (path, params) = target.split_once('?').unwrap_or((target, ""));
+    if path != "/search" {
+        return None;
+    }
+    let q = params
+        .split('&')
+        .find_map(|param| param.strip_prefix("q="))
+        .unwrap_or_default()
+        .replace('+', " ");
+    Some(percent_decode_str(&q).decode_utf8_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::config::Config;
+    use crate::provider::{DocProvider, DocSet, DynProvider, EntryType, SearchEntry, SearchOptions};
+
+    use super::*;
+
+    /// Serves a `rust` doc set holding `Vec`.
+    struct MockProvider;
+
+    #[async_trait]
+    impl DocProvider for MockProvider {
+        fn name(&self) -> &str {
+            "Dash"
+        }
+
+        async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>> {
+            Ok(vec![DocSet {
+                id: Arc::from("rust"),
+                keywords: vec![Arc::from("rust")],
+                name: Arc::from("Rust"),
+                description: Arc::from(""),
+                icon: Arc::from(""),
+                homepage: None,
+            }])
+        }
+
+        async fn search_doc_sets(&self, keyword: &str) -> anyhow::Result<Vec<DocSet>> {
+            let doc_sets = self.doc_sets().await?;
+            Ok(doc_sets.into_iter().filter(|ds| ds.keyword_relevance(keyword).is_some()).collect())
+        }
+
+        async fn search(&self, _doc_set_id: &str, q: &str, _options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>> {
+            let entry = SearchEntry {
+                entry_type: EntryType::Struct,
+                title: Arc::from("Vec"),
+                desc: Arc::from(""),
+                id: Arc::from("std/vec/struct.Vec.html"),
+                relevance: 100,
+            };
+            Ok(Some(entry).filter(|e| e.title.eq_ignore_ascii_case(q)).into_iter().collect())
+        }
+
+        async fn open(&self, _doc_set_id: &str, _entry_url: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn resolve_url(&self, _doc_set_id: &str, entry_url: &str) -> Option<String> {
+            Some(format!("https://doc.rust-lang.org/{entry_url}"))
+        }
+
+        async fn clean_up(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn get(port: u16, target: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream.write_all(format!("GET {target} HTTP/1.1\r\n\r\n").as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[test]
+    fn reads_the_query_of_search_targets_only() {
+        assert_eq!(search_query("/search?q=rust+vec%3A%3Anew"), Some("rust vec::new".to_string()));
+        assert_eq!(search_query("/search?limit=1&q=rust"), Some("rust".to_string()));
+        assert_eq!(search_query("/search"), Some(String::new()));
+        assert_eq!(search_query("/find?q=rust"), None);
+    }
+
+    #[tokio::test]
+    async fn answers_searches_with_json() {
+        let plugin = KRunnerPlugin::new(vec![Arc::new(MockProvider) as DynProvider], Arc::new(Config::default())).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve(listener, Arc::new(plugin)));
+
+        let response = get(port, "/search?q=rust+vec").await;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{head}");
+        assert!(head.contains("Content-Type: application/json\r\n"));
+        let hits: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(hits.as_array().unwrap().len(), 1);
+        assert_eq!(hits[0]["provider"], "Dash");
+        assert_eq!(hits[0]["doc_set"], "rust");
+        assert_eq!(hits[0]["title"], "Vec");
+        assert_eq!(hits[0]["type"], "Struct");
+        assert!(hits[0]["relevance"].as_f64().unwrap() > 0.0);
+        assert_eq!(hits[0]["url"], "https://doc.rust-lang.org/std/vec/struct.Vec.html");
+
+        assert!(get(port, "/find?q=rust+vec").await.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
@@ -0,0 +1,21 @@
+/// Fills an import `template` for `symbol`, or returns the symbol as it is without a template.
+///
+/// `{name}` is the symbol as the doc set lists it, `{last}` its final segment and `{module}`
+/// everything before that, segments being separated by `::` or `.`. So with `std::vec::Vec`,
+/// `use {name};` gives `use std::vec::Vec;` and with `typing.List`, `from {module} import {last}`
+/// gives `from typing import List`.
+pub fn import_snippet(template: Option<&str>, symbol: &str) -> String {
+    let template = match template {
+        Some(v) => v,
+        None => return symbol.to_string(),
+    };
+
+    let (module, last) = symbol
+        .rsplit_once("::")
+        .or_else(|| symbol.rsplit_once('.'))
+        .unwrap_or(("", symbol));
+    template
+        .replace("{name}", symbol)
+        .replace("{module}", module)
+        .replace("{last}", last)
+}
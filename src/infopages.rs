@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use tokio::task::spawn_blocking;
+
+use crate::config::InfoConfig;
+use crate::logging::debug_throttled;
+use crate::opener;
+use crate::provider::{name_relevance, DocProvider, DocSet, EntryType, SearchEntry, SearchOptions};
+
+const DOC_SET_ID: &str = "info";
+/// A top level topic listed in an info `dir` file.
+#[derive(Debug, PartialEq, Eq)]
+struct InfoTopic {
+    name: Arc<str>,
+    /// Node reference as understood by `info`, e.g. `(coreutils)ls invocation`.
+    node: Arc<str>,
+    desc: Arc<str>,
+}
+
+/// Serves the GNU info topics listed in the system `dir` files as a single `info` doc set.
+pub struct InfoPages {
+    topics: Vec<InfoTopic>,
+    terminal: Vec<String>,
+}
+
+impl InfoPages {
+    pub async fn new(config: &InfoConfig) -> anyhow::Result<Self> {
+        let dir_files: Vec<PathBuf> = config.dirs.iter().map(|d| d.join("dir")).collect();
+        let topics = spawn_blocking(move || {
+            let mut topics = vec![];
+            for file in dir_files {
+                match std::fs::read_to_string(&file) {
+                    Ok(content) => topics.extend(content.lines().filter_map(parse_dir_line)),
+                    Err(e) => log::debug!("Skipping info dir file {}: {e}", file.display()),
+                }
+            }
+            topics.sort_by(|a: &InfoTopic, b| a.name.cmp(&b.name));
+            topics.dedup_by(|a, b| a.node == b.node);
+            topics
+        }).await.context("Reading info dir files")?;
+
+        log::debug!("Found {} info topics", topics.len());
+        Ok(Self {
+            topics,
+            terminal: config.terminal.clone(),
+        })
+    }
+
+    fn to_doc_set(&self) -> DocSet {
+        DocSet {
+            id: DOC_SET_ID.into(),
+            keywords: vec![DOC_SET_ID.into()],
+            name: DOC_SET_ID.into(),
+            description: Arc::from("GNU info pages"),
+            icon: Arc::from("help-contents"),
+            homepage: None,
+        }
+    }
+}
+
+/// Parses a menu line of a `dir` file, e.g.
+/// `* Coreutils: (coreutils).       Core GNU (file, text, shell) utilities.`
+fn parse_dir_line(line: &str) -> Option<InfoTopic> {
+    let rest = line.strip_prefix("* ")?;
+    let (name, rest) = rest.split_once(':')?;
+    let rest = rest.trim_start().strip_prefix('(')?;
+    let (file, rest) = rest.split_once(')')?;
+    let (node, desc) = rest.split_once('.')?;
+
+    Some(InfoTopic {
+        name: name.trim().into(),
+        node: format!("({file}){}", node.trim()).into(),
+        desc: desc.trim().into(),
+    })
+}
+
+#[async_trait]
+impl DocProvider for InfoPages {
+    fn name(&self) -> &str {
+        "info"
+    }
+
+    fn icon(&self) -> &str {
+        "help-contents"
+    }
+
+    async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>> {
+        if self.topics.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![self.to_doc_set()])
+    }
+
+    async fn search_doc_sets(&self, keyword: &str) -> anyhow::Result<Vec<DocSet>> {
+        if !self.supports_keyword(keyword) {
+            return Ok(vec![]);
+        }
+        self.doc_sets().await
+    }
+
+    fn supports_keyword(&self, keyword: &str) -> bool {
+        let keyword = keyword.trim().to_lowercase();
+        !keyword.is_empty() && DOC_SET_ID.starts_with(&keyword)
+    }
+
+    async fn search(&self, doc_set_id: &str, q: &str, options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>> {
+        if doc_set_id != DOC_SET_ID {
+            return Ok(vec![]);
+        }
+
+        let q = options.fold(q.trim());
+        let mut rs: Vec<SearchEntry> = self.topics
+            .iter()
+            .filter_map(|topic| {
+                let relevance = name_relevance(&options.fold(&topic.name), &q)?;
+                Some(SearchEntry {
+                    entry_type: EntryType::Guide,
+                    title: topic.name.clone(),
+                    desc: topic.desc.clone(),
+                    id: topic.node.clone(),
+                    relevance,
+                })
+            })
+            .collect();
+
+        rs.sort_by(|a, b| b.relevance.cmp(&a.relevance).then(a.title.len().cmp(&b.title.len())));
+        rs.truncate(options.limit);
+        debug_throttled!("Searching info for {q} got {} results", rs.len());
+        Ok(rs)
+    }
+
+    async fn open(&self, _doc_set_id: &str, entry_url: &str) -> anyhow::Result<()> {
+        let (program, args) = self.terminal.split_first().context("No terminal configured for info pages")?;
+        let args: Vec<&str> = args
+            .iter()
+            .map(String::as_str)
+            .chain(["info", entry_url])
+            .collect();
+        opener::spawn_detached(program, &args)
+    }
+
+    async fn clean_up(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
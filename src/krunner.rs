@@ -1,43 +1,645 @@
-use std::{collections::HashMap, sync::Arc, vec};
+use std::{collections::{HashMap, VecDeque}, sync::Arc, time::Duration, vec};
+use std::ops::Range;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
 use anyhow::Context;
 
 use maplit::hashmap;
 use serde::{Deserialize, Serialize};
-use tokio::task::{JoinError, JoinSet};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::{watch, Notify, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{sleep, timeout, timeout_at, Instant};
 use zbus::{
     dbus_interface,
     fdo::Result,
     zvariant::{Type, Value},
+    Connection,
     ConnectionBuilder,
 };
 use zbus::fdo::Error;
 
-use crate::provider::{DocProvider, DocSet, EntryType, SearchEntry};
+use crate::config::{Config, EntryIconMode, GroupBy, HighlightMode, TrimPrefix};
+use crate::control::Control;
+use crate::error::DocError;
+use crate::feeds::Feeds;
+use crate::focus::{self, DynFocusSource, KWinFocus};
+use crate::httpapi;
+use crate::imports;
+use crate::logging::debug_throttled;
+use crate::opener;
+use crate::provider::{DocSet, DynProvider, EntryType, SearchEntry, SearchOptions};
+use crate::registry::ProviderSet;
+use crate::usage::Usage;
 
 pub struct KRunnerPlugin {
-    providers: Vec<Arc<dyn DocProvider + Send + Sync + 'static>>,
+    providers: ProviderSet,
+    config: Arc<Config>,
+    debouncer: Debouncer,
+    /// Bounds how many doc sets are searched at once across all queries.
+    search_permits: Arc<Semaphore>,
+    /// Notified on every `Match` and `Run` call, see [ServedPlugin::serve].
+    activity: Arc<Notify>,
+    /// Where the last query's results start, see `SearchConfig::paginate`.
+    page: Mutex<Page>,
+    usage: Arc<Usage>,
+    /// Where the focused application comes from, see `SearchConfig::focused_prefix`.
+    focus: Option<DynFocusSource>,
 }
 
-impl KRunnerPlugin {
-    pub async fn new(
-        providers: Vec<Arc<dyn DocProvider + Send + Sync + 'static>>,
-        object_path: &str,
-    ) -> anyhow::Result<()> {
-        ConnectionBuilder::session()?
+/// The results page of a query, advanced by its "more results" entry and back to the first
+/// page as soon as another query comes in.
+#[derive(Default)]
+struct Page {
+    query: String,
+    offset: usize,
+}
+
+/// Coalesces bursts of `Match` calls into a single in-flight slot. KRunner only shows the
+/// newest query, so only the last call of a burst runs and older callers get its results.
+struct Debouncer {
+    latest: AtomicU64,
+    results: watch::Sender<(u64, Arc<Vec<QueryEntry>>)>,
+}
+
+impl Default for Debouncer {
+    fn default() -> Self {
+        Self {
+            latest: AtomicU64::new(0),
+            results: watch::channel((0, Arc::new(vec![]))).0,
+        }
+    }
+}
+
+impl Debouncer {
+    /// Publishes `rs` unless a newer call already published its own.
+    fn publish(&self, generation: u64, rs: Arc<Vec<QueryEntry>>) {
+        self.results.send_if_modified(|published| match published.0 < generation {
+            true => {
+                *published = (generation, rs);
+                true
+            }
+            false => false,
+        });
+    }
+}
+
+/// Publishes no results for its call when dropped, so the calls it superseded don't wait
+/// forever on one that was cancelled or panicked before publishing.
+struct PublishOnDrop<'a> {
+    debouncer: &'a Debouncer,
+    generation: u64,
+}
+
+impl Drop for PublishOnDrop<'_> {
+    fn drop(&mut self) {
+        self.debouncer.publish(self.generation, Arc::default());
+    }
+}
+
+/// Configures and registers a [KRunnerPlugin] on the session bus.
+pub struct KRunnerPluginBuilder {
+    providers: Vec<DynProvider>,
+    feeds: Option<Feeds>,
+    config: Arc<Config>,
+    object_path: String,
+}
+
+impl KRunnerPluginBuilder {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            providers: vec![],
+            feeds: None,
+            config,
+            object_path: "/krunner".into(),
+        }
+    }
+
+    pub fn providers(mut self, providers: impl IntoIterator<Item = DynProvider>) -> Self {
+        self.providers.extend(providers);
+        self
+    }
+
+    pub fn feeds(mut self, feeds: Option<Feeds>) -> Self {
+        self.feeds = feeds;
+        self
+    }
+
+    pub fn object_path(mut self, object_path: impl Into<String>) -> Self {
+        self.object_path = object_path.into();
+        self
+    }
+
+    /// Registers the plugin and its control interface, the plugin is served for as long as the
+    /// returned [ServedPlugin] is alive.
+    pub async fn build(self) -> anyhow::Result<ServedPlugin> {
+        let Self { providers, feeds, config, object_path } = self;
+        let providers = ProviderSet::new(providers)?;
+        let focus = KWinFocus::default();
+        let follow_focus = !config.search.focused_prefix.is_empty();
+        let mut plugin = KRunnerPlugin::with_provider_set(providers.clone(), config.clone());
+        if follow_focus {
+            plugin = plugin.with_focus(Arc::new(focus.clone()));
+        }
+        let activity = plugin.activity.clone();
+        // The endpoint is an extra, KRunner is still served without it
+        if config.http.enabled {
+            if let Err(e) = httpapi::start(plugin.share(), config.http.port).await {
+                log::error!("Not serving the search endpoint: {e:?}");
+            }
+        }
+        let connection = ConnectionBuilder::session()?
             .name("dev.fanchao.DashDoc")?
-            .serve_at(object_path, Self { providers })?
+            .serve_at(object_path.as_str(), Control::new(providers.clone(), feeds, config))?
+            .serve_at(object_path.as_str(), plugin)?
+            .serve_at(object_path.as_str(), focus.clone())?
             .build()
             .await?;
+        if follow_focus {
+            // Without the script the prefix finds no application and queries are searched as typed
+            if let Err(e) = focus.install(&connection, &object_path).await {
+                log::warn!("Unable to follow the focused application through KWin: {e:?}");
+            }
+        }
 
-        Ok(())
+        Ok(ServedPlugin { _connection: connection, providers, activity })
+    }
+}
+
+/// A plugin registered on the session bus.
+pub struct ServedPlugin {
+    _connection: Connection,
+    providers: ProviderSet,
+    activity: Arc<Notify>,
+}
+
+impl ServedPlugin {
+    /// Serves until no `Match` or `Run` call arrived for `idle_timeout`, then cleans the
+    /// providers up. A zero timeout serves forever.
+    pub async fn serve(self, idle_timeout: Duration) {
+        if idle_timeout.is_zero() {
+            return std::future::pending().await;
+        }
+
+        clean_up_when_idle(&self.activity, &self.providers, idle_timeout).await;
+    }
+}
+
+/// Waits until `activity` was quiet for `idle_timeout`, then cleans the providers up.
+async fn clean_up_when_idle(activity: &Notify, providers: &ProviderSet, idle_timeout: Duration) {
+    while timeout(idle_timeout, activity.notified()).await.is_ok() {}
+    log::info!("No query for {idle_timeout:?}, shutting down");
+    clean_up(providers).await;
+}
+
+async fn clean_up(providers: &ProviderSet) {
+    for p in providers.snapshot().iter() {
+        if let Err(e) = p.clean_up().await {
+            log::error!("Error cleaning up provider {}: {e:?}", p.name());
+        }
+    }
+}
+
+impl KRunnerPlugin {
+    /// A plugin that isn't registered on any bus, for running queries in-process. Use
+    /// [KRunnerPluginBuilder] to serve it. Fails when two providers share a name.
+    pub fn new(providers: Vec<DynProvider>, config: Arc<Config>) -> anyhow::Result<Self> {
+        Ok(Self::with_provider_set(ProviderSet::new(providers)?, config))
+    }
+
+    fn with_provider_set(providers: ProviderSet, config: Arc<Config>) -> Self {
+        Self {
+            providers,
+            search_permits: Arc::new(Semaphore::new(config.search.max_concurrent_searches.max(1))),
+            activity: Default::default(),
+            page: Default::default(),
+            usage: Arc::new(Usage::load(config.search.usage_file())),
+            config,
+            debouncer: Default::default(),
+            focus: None,
+        }
+    }
+
+    /// Searches the doc set of the application `focus` reports for queries starting with
+    /// `SearchConfig::focused_prefix`.
+    pub fn with_focus(mut self, focus: DynFocusSource) -> Self {
+        self.focus = Some(focus);
+        self
+    }
+
+    /// A plugin serving queries from elsewhere than the bus, sharing this one's providers,
+    /// search permits and usage stats so both draw on the same limits.
+    fn share(&self) -> Self {
+        Self {
+            providers: self.providers.clone(),
+            config: self.config.clone(),
+            debouncer: Default::default(),
+            search_permits: self.search_permits.clone(),
+            activity: self.activity.clone(),
+            page: Default::default(),
+            usage: self.usage.clone(),
+            focus: self.focus.clone(),
+        }
+    }
+
+    /// The doc entries [KRunnerPlugin::matches] finds for `query`, each with the URL it opens,
+    /// for callers presenting them without KRunner. Keyword completions are left out.
+    pub async fn search(&self, query: &str) -> Vec<SearchHit> {
+        self.activity.notify_one();
+        let mut hits = vec![];
+        for entry in self.matches(query, QueryMode::Normal).await {
+            let (provider, doc_set, url, title, entry_type) = match serde_json::from_str::<EntryData>(&entry.data) {
+                Ok(EntryData::Entry { provider, doc_set_id, url, title, entry_type }) => (provider, doc_set_id, url, title, entry_type),
+                _ => continue,
+            };
+            let url = match self.providers.find(&provider) {
+                Some(p) => p.resolve_url(&doc_set, &url).await,
+                None => None,
+            };
+            hits.push(SearchHit { provider, doc_set, title, entry_type, relevance: entry.relevance, url });
+        }
+        hits
+    }
+
+    /// Resolves a KRunner query into sorted matches. This is everything behind the `Match`
+    /// D-Bus method, callable without a bus connection.
+    pub async fn matches(&self, query: &str, mode: QueryMode) -> Vec<QueryEntry> {
+        let query = match sanitize_query(query, self.config.search.max_query_len) {
+            Some(query) => query,
+            None => {
+                debug_throttled!("Ignoring a query of {} characters", query.chars().count());
+                return vec![];
+            }
+        };
+        let query = match self.focused_query(&query).await {
+            Some(focused) => focused,
+            None => query,
+        };
+        debug_throttled!("Querying {query}");
+        // Timings are only taken when they would actually be logged
+        let timed = log::log_enabled!(log::Level::Debug);
+        let started = timed.then(Instant::now);
+        let full_query = query.trim();
+        let offset = match self.config.search.paginate {
+            true => self.page_offset(full_query),
+            false => 0,
+        };
+
+        let (kw, query, case_sensitive, global) = match query.trim().strip_prefix(self.config.search.global_prefix.as_str()) {
+            Some(rest) if !self.config.search.global_prefix.is_empty() => {
+                let (term, case_sensitive) = split_term(rest);
+                ("", term, case_sensitive, true)
+            }
+            _ => match split_query(&query) {
+                Some((kw, term, case_sensitive)) => (kw, term, case_sensitive, false),
+                None => return vec![],
+            },
+        };
+
+        // `dash:python vec` only asks the Dash provider, an unknown prefix is part of the keyword
+        let providers = self.providers.snapshot();
+        let (only_provider, kw) = match kw.split_once(':') {
+            Some((prefix, rest)) if !rest.is_empty() && providers.iter().any(|p| p.name().eq_ignore_ascii_case(prefix)) => {
+                (Some(prefix), rest)
+            }
+            _ => (None, kw),
+        };
+
+        // A first word no doc set answers to is where the search term starts instead
+        let (kw, query, case_sensitive) = match self.config.search.default_keyword.as_str() {
+            default if !default.is_empty() && !global && only_provider.is_none() && !is_keyword(&providers, kw) => {
+                let (term, case_sensitive) = split_term(full_query);
+                (default, term, case_sensitive)
+            }
+            _ => (kw, query, case_sensitive),
+        };
+        let (query, hidden_types) = match query.strip_prefix(ALL_TYPES_PREFIX) {
+            Some(rest) => (rest, vec![]),
+            None => (query, self.config.search.hidden_types.clone()),
+        };
+
+        match global {
+            // Listing every doc set instead of searching it would be useless
+            true if query.is_empty() => return vec![],
+            false if kw.chars().count() < mode.min_letters(&self.config) => return vec![],
+            _ => {}
+        }
+        let case_sensitive = case_sensitive || self.config.search.case_sensitive;
+        let global_limit = match (global, query.is_empty()) {
+            (true, _) => self.config.search.global_search_limit,
+            (false, true) => self.config.search.completion_limit,
+            (false, false) => mode.global_limit(&self.config),
+        };
+
+        // Slower doc sets show up on the next keystroke instead of holding back fast ones
+        let deadline = Instant::now() + Duration::from_millis(self.config.search.timeout_ms);
+        let ctx = Arc::new(SearchContext {
+            kw: kw.into(),
+            query: query.into(),
+            options: SearchOptions {
+                // Later pages need the entries of the earlier ones too
+                limit: self.config.search.per_docset_limit + offset,
+                case_sensitive,
+                fold_diacritics: self.config.search.fold_diacritics,
+                hidden_types,
+                global,
+            },
+            config: self.config.clone(),
+            permits: self.search_permits.clone(),
+            usage: self.usage.clone(),
+            provider_deadline: deadline.checked_sub(PROVIDER_DEADLINE_MARGIN).unwrap_or(deadline),
+            provider_limit: self.config.search.per_provider_limit + offset,
+        });
+
+        // Search concurrently in all providers, each task sends its best matches once its doc sets are done
+        let (tx, mut rx) = unbounded_channel();
+        let mut task_set = JoinSet::new();
+        let wanted = providers
+            .iter()
+            .filter(|p| only_provider.is_none_or(|name| p.name().eq_ignore_ascii_case(name)))
+            .filter(|p| global || p.supports_keyword(kw));
+        for p in wanted {
+            let p = p.clone();
+            let ctx = ctx.clone();
+            let tx = tx.clone();
+            task_set.spawn(async move {
+                let provider_started = timed.then(Instant::now);
+                let doc_sets = match global {
+                    true => p.doc_sets().await,
+                    false => p.search_doc_sets(ctx.kw.as_ref()).await,
+                };
+                let doc_sets = match doc_sets {
+                    Ok(doc_sets) if !doc_sets.is_empty() => doc_sets,
+                    Ok(_) => return,
+                    Err(e) => {
+                        log::error!("Error searching doc provider(name={}): {e:?}", p.name());
+                        return;
+                    }
+                };
+                if let Some(t) = provider_started {
+                    debug_throttled!("Provider {}: matched {} doc sets in {:?}", p.name(), doc_sets.len(), t.elapsed());
+                }
+
+                // Allocated once, every entry of this provider shares it
+                let provider_name: Arc<str> = p.name().into();
+                let provider_icon: Arc<str> = p.icon().into();
+                if ctx.query.is_empty() {
+                    // Doc sets named exactly by the keyword come first and survive the cap
+                    let weight = |ds: &DocSet| completion_weight(ds, &provider_name, &ctx);
+                    let mut doc_sets = doc_sets;
+                    doc_sets.sort_by(|a, b| weight(b).total_cmp(&weight(a)));
+                    // Doc sets announced with the same keyword and title are told apart by id
+                    let announced: Vec<_> = doc_sets
+                        .iter()
+                        .map(|ds| (ds.keywords.first().cloned(), completion_title(ds)))
+                        .collect();
+                    let ambiguous: Vec<bool> = announced
+                        .iter()
+                        .map(|a| announced.iter().filter(|b| *b == a).count() > 1)
+                        .collect();
+                    let _ = tx.send(doc_sets
+                        .into_iter()
+                        .zip(ambiguous)
+                        .flat_map(|(ds, ambiguous)| {
+                            let relevance = COMPLETION_RELEVANCE * weight(&ds);
+                            let title = match ambiguous {
+                                true => format!("{} ({})", completion_title(&ds), ds.id),
+                                false => completion_title(&ds).to_string(),
+                            };
+                            let DocSet { id, keywords, icon, .. } = ds;
+                            let icon = match icon.is_empty() {
+                                true => provider_icon.clone(),
+                                false => icon,
+                            };
+                            let data: Arc<str> = serde_json::to_string(&EntryData::DocSet {
+                                provider: provider_name.clone(),
+                                id,
+                            }).unwrap().into();
+                            let index_entry = QueryEntry {
+                                data: data.clone(),
+                                display_text: format!("Open {title} index").into(),
+                                icon_name: icon.clone(),
+                                match_type: MATCH_TYPE_COMPLETION,
+                                relevance,
+                                properties: Default::default(),
+                            };
+                            // Aliases still match, but only the primary keyword is suggested
+                            keywords.into_iter().take(1).map(move |keyword| QueryEntry {
+                                data: data.clone(),
+                                display_text: format!("Type \"{keyword} keyword\" to search {title}").into(),
+                                icon_name: icon.clone(),
+                                match_type: MATCH_TYPE_COMPLETION,
+                                relevance,
+                                properties: Default::default(),
+                            }).chain([index_entry])
+                        })
+                        .take(ctx.config.search.completion_limit)
+                        .map(|entry| Ranked { doc_set: None, entry })
+                        .collect());
+                    return;
+                }
+
+                let count = search_in_doc_sets(p.clone(), provider_name, doc_sets, ctx, tx).await;
+                if let Some(t) = provider_started {
+                    debug_throttled!("Provider {}: searched {count} results in {:?}", p.name(), t.elapsed());
+                }
+            });
+        }
+        drop(tx);
+
+        // Nothing a slower provider finds can outrank a full page of perfect matches
+        let enough = match self.config.search.early_return_matches.min(global_limit) {
+            0 => usize::MAX,
+            n => n + offset,
+        };
+        let mut rs = collect_until(deadline, &mut rx, |rs| {
+            rs.iter().filter(|r| r.entry.match_type == MATCH_TYPE_EXACT && r.entry.relevance >= PERFECT_RELEVANCE).count() >= enough
+        }).await;
+        // Dropping the set aborts the providers that missed the deadline, along with their doc set searches
+        drop(task_set);
+
+        let merge_started = timed.then(Instant::now);
+        rs.sort_by(|a, b| b.entry.match_type.cmp(&a.entry.match_type).then(b.entry.relevance.total_cmp(&a.entry.relevance)));
+        if self.config.search.interleave && !query.is_empty() {
+            rs = interleave(rs);
+        }
+        let more = rs.len() > offset + global_limit;
+        let mut rs: Vec<QueryEntry> = rs.into_iter().skip(offset).take(global_limit).map(|r| r.entry).collect();
+        if more && self.config.search.paginate && !query.is_empty() {
+            rs.push(QueryEntry {
+                data: serde_json::to_string(&EntryData::More {
+                    query: full_query.into(),
+                    offset: offset + global_limit,
+                }).unwrap().into(),
+                display_text: format!("Show more results for \"{query}\"").into(),
+                icon_name: Arc::from("go-down"),
+                match_type: MATCH_TYPE_COMPLETION,
+                relevance: 0.0,
+                properties: hashmap! {
+                    QueryPropertyField::Actions => Vec::<String>::new().into(),
+                },
+            });
+        }
+        if let (Some(merge_started), Some(started)) = (merge_started, started) {
+            debug_throttled!(
+                "Query {query:?} returned {} results in {:?} (merge/sort {:?})",
+                rs.len(),
+                started.elapsed(),
+                merge_started.elapsed(),
+            );
+        }
+        rs
+    }
+
+    /// `query` with the focused prefix replaced by the keyword of the focused application, `None`
+    /// when it doesn't start with the prefix or the application maps to no keyword.
+    async fn focused_query(&self, query: &str) -> Option<String> {
+        let prefix = self.config.search.focused_prefix.as_str();
+        let term = query.trim().strip_prefix(prefix).filter(|_| !prefix.is_empty())?;
+        let class = self.focus.as_ref()?.window_class().await?;
+        let keyword = focus::keyword_for_class(&self.config.window_keywords, &class)?;
+        Some(format!("{keyword} {term}"))
+    }
+
+    /// Where the results of `query` start, back at the first page when it isn't the query the
+    /// current page belongs to.
+    fn page_offset(&self, query: &str) -> usize {
+        let mut page = self.page.lock().unwrap();
+        if page.query != query {
+            *page = Page { query: query.to_string(), offset: 0 };
+        }
+        page.offset
+    }
+}
+
+/// Whether any doc set of `providers` answers to `kw`, going by the keywords they keep in memory.
+fn is_keyword(providers: &[DynProvider], kw: &str) -> bool {
+    providers.iter().any(|p| p.supports_keyword(kw))
+}
+
+/// How the runner was invoked.
+///
+/// KRunner's single runner mode (the user picked this runner explicitly) isn't passed by any
+/// revision of the `org.kde.krunner1` interface we serve, so D-Bus queries always run in
+/// [QueryMode::Normal] and `MinLetterCount` reports the normal minimum. Explicit invocations
+/// run in [QueryMode::SingleRunner], which accepts any keyword length and returns more results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueryMode {
+    #[default]
+    Normal,
+    SingleRunner,
+}
+
+impl QueryMode {
+    fn min_letters(&self, config: &Config) -> usize {
+        match self {
+            QueryMode::Normal => config.search.min_letters,
+            QueryMode::SingleRunner => config.search.single_runner_min_letters,
+        }
+    }
+
+    fn global_limit(&self, config: &Config) -> usize {
+        match self {
+            QueryMode::Normal => config.search.global_limit,
+            QueryMode::SingleRunner => config.search.single_runner_global_limit,
+        }
     }
 }
 
 type MatchType = i32;
 
+const ACTION_REVEAL_FILE: &str = "reveal_file";
+const ACTION_COPY_IMPORT: &str = "copy_import";
+const ACTION_OPEN_SOURCE: &str = "open_source";
+
 const MATCH_TYPE_COMPLETION: MatchType = 10;
 const MATCH_TYPE_EXACT: MatchType = 100;
 
+/// Relevance policy: KRunner combines `match_type` and `relevance`, so both are kept consistent.
+/// Doc entries map their 0–100 score into `ENTRY_RELEVANCE_FLOOR..=1.0` and keyword completions
+/// sit below that band, which means any real match outranks a completion under either signal.
+const COMPLETION_RELEVANCE: f64 = 0.05;
+const ENTRY_RELEVANCE_FLOOR: f64 = 0.1;
+
+/// What an exact name match scores in a doc set named exactly by the keyword.
+const PERFECT_RELEVANCE: f64 = 1.0;
+
+fn entry_relevance(score: usize, doc_set_weight: f64) -> f64 {
+    ENTRY_RELEVANCE_FLOOR + (1.0 - ENTRY_RELEVANCE_FLOOR) * (score.min(100) as f64 / 100.0) * doc_set_weight
+}
+
+/// Scales a doc set's entries by how well the typed keyword matches it. Global searches have no
+/// keyword and weigh every doc set the same.
+fn doc_set_weight(doc_set: &DocSet, keyword: &str) -> f64 {
+    match keyword.is_empty() {
+        true => 1.0,
+        false => doc_set.keyword_relevance(keyword).unwrap_or_default(),
+    }
+}
+
+/// Orders keyword completions: [doc_set_weight], averaged with how much the doc set is used
+/// when completions are sorted by usage.
+fn completion_weight(doc_set: &DocSet, provider: &str, ctx: &SearchContext) -> f64 {
+    let weight = doc_set_weight(doc_set, &ctx.kw);
+    match ctx.config.search.sort_completions_by_usage {
+        true => (weight + ctx.usage.score(provider, &doc_set.id)) / 2.0,
+        false => weight,
+    }
+}
+
+/// Turns control characters (a pasted newline or tab) into spaces, `None` when the trimmed
+/// query is longer than `max_len` characters. A `max_len` of 0 allows any length.
+fn sanitize_query(query: &str, max_len: usize) -> Option<String> {
+    let query: String = query
+        .chars()
+        .map(|c| match c.is_control() {
+            true => ' ',
+            false => c,
+        })
+        .collect();
+    match max_len > 0 && query.trim().chars().count() > max_len {
+        true => None,
+        false => Some(query),
+    }
+}
+
+/// Starting the search term with this matches case-exactly for that query only.
+const CASE_SENSITIVE_PREFIX: &str = "==";
+/// Starting the search term with this shows the types hidden by `SearchConfig::hidden_types`
+/// for that query only, e.g. `rust *vec`. Goes after [CASE_SENSITIVE_PREFIX] when both are used.
+const ALL_TYPES_PREFIX: &str = "*";
+
+/// Splits a query into its keyword, its search term and whether the term asked for a
+/// case-exact match, see [split_term].
+fn split_query(query: &str) -> Option<(&str, &str, bool)> {
+    let query = query.trim();
+    if query.is_empty() {
+        return None;
+    }
+
+    let (kw, rest) = match query.split_once(|c: char| c.is_ascii_whitespace()) {
+        Some((kw, rest)) => (kw, rest),
+        None => (query, ""),
+    };
+    let (term, case_sensitive) = split_term(rest);
+    Some((kw, term, case_sensitive))
+}
+
+/// Picks the search term out of what follows the keyword: the next word, or a double-quoted
+/// phrase searched with its spaces, e.g. `rust "into iter"`. A quote that is never closed is
+/// searched literally.
+fn split_term(rest: &str) -> (&str, bool) {
+    let rest = rest.trim_start();
+    let (rest, case_sensitive) = match rest.strip_prefix(CASE_SENSITIVE_PREFIX) {
+        Some(rest) => (rest, true),
+        None => (rest, false),
+    };
+    let term = match rest.strip_prefix('"').and_then(|r| r.split_once('"')) {
+        Some((phrase, _)) => phrase,
+        None => rest.split_ascii_whitespace().next().unwrap_or_default(),
+    };
+    (term, case_sensitive)
+}
 
 #[derive(Serialize, Debug, Type, Clone, Eq, PartialEq, Hash)]
 #[zvariant(signature = "s")]
@@ -46,10 +648,12 @@ enum QueryPropertyField {
     Category,
     // Urls,
     Subtext,
+    /// Ids of the secondary actions offered on the match, all of them when unset.
+    Actions,
 }
 
-#[derive(Serialize, Type)]
-struct QueryEntry {
+#[derive(Serialize, Type, Clone)]
+pub struct QueryEntry {
     data: Arc<str>,
     display_text: Arc<str>,
     icon_name: Arc<str>,
@@ -58,6 +662,47 @@ struct QueryEntry {
     properties: HashMap<QueryPropertyField, Value<'static>>,
 }
 
+/// A match on its way to KRunner, with the doc set it came from so [interleave] can group it
+/// without parsing its data back.
+struct Ranked {
+    doc_set: Option<DocSetKey>,
+    entry: QueryEntry,
+}
+
+/// The provider name and id of a doc set.
+type DocSetKey = (Arc<str>, Arc<str>);
+
+/// A doc entry as returned by [KRunnerPlugin::search].
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub provider: Arc<str>,
+    pub doc_set: Arc<str>,
+    pub title: Arc<str>,
+    #[serde(rename = "type")]
+    pub entry_type: EntryType,
+    pub relevance: f64,
+    pub url: Option<String>,
+}
+
+impl QueryEntry {
+    /// The match as plain JSON, with its properties unwrapped from their D-Bus variants.
+    pub fn to_json(&self) -> serde_json::Value {
+        let property = |field| match self.properties.get(&field) {
+            Some(Value::Str(s)) => Some(s.as_str().to_string()),
+            _ => None,
+        };
+        serde_json::json!({
+            "data": self.data.as_ref(),
+            "display_text": self.display_text.as_ref(),
+            "icon_name": self.icon_name.as_ref(),
+            "match_type": self.match_type,
+            "relevance": self.relevance,
+            "category": property(QueryPropertyField::Category),
+            "subtext": property(QueryPropertyField::Subtext),
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
 enum EntryData {
     DocSet {
@@ -68,6 +713,13 @@ enum EntryData {
         provider: Arc<str>,
         doc_set_id: Arc<str>,
         url: Arc<str>,
+        title: Arc<str>,
+        entry_type: EntryType,
+    },
+    /// The "more results" entry: the next `Match` of `query` starts at `offset`.
+    More {
+        query: Arc<str>,
+        offset: usize,
     },
 }
 
@@ -77,158 +729,1074 @@ type VariantMap<'a> = HashMap<&'a str, Value<'static>>;
 impl KRunnerPlugin {
     #[dbus_interface(name = "Match")]
     async fn query(&self, query: &str) -> Result<Vec<QueryEntry>> {
-        log::debug!("Querying {query}");
-
-        let mut splits = query.trim().split_ascii_whitespace();
-        let (kw, query) = match (splits.next(), splits.next()) {
-            (Some(v), q) => (v, q.unwrap_or_default()),
-            _ => return Ok(vec![]),
-        };
-
-        if kw.len() < 1 {
-            return Ok(vec![]);
+        self.activity.notify_one();
+        let window = Duration::from_millis(self.config.search.debounce_ms);
+        if window.is_zero() {
+            return Ok(self.matches(query, QueryMode::Normal).await);
         }
 
-        let kw: Arc<str> = kw.into();
-        let query: Arc<str> = query.into();
+        let generation = self.debouncer.latest.fetch_add(1, Ordering::SeqCst) + 1;
+        let _guard = PublishOnDrop { debouncer: &self.debouncer, generation };
+        let mut results = self.debouncer.results.subscribe();
+        sleep(window).await;
 
-        // Search concurrently in all providers
-        let mut task_set = JoinSet::new();
-        for p in &self.providers {
-            let kw = kw.clone();
-            let p = p.clone();
-            let query = query.clone();
-            task_set.spawn(async move {
-                let doc_sets = match p.search_doc_sets(kw.as_ref()).await {
-                    Ok(doc_sets) if !doc_sets.is_empty() => doc_sets,
-                    Ok(_) => return vec![],
-                    Err(e) => {
-                        log::error!("Error searching doc provider(name={}): {e:?}", p.name());
-                        return vec![];
-                    }
-                };
-
-                if query.is_empty() {
-                    return doc_sets
-                        .into_iter()
-                        .flat_map(
-                            |DocSet {
-                                 id,
-                                 name,
-                                 keywords,
-                                 icon,
-                                 ..
-                             }| {
-                                let provider_name: Arc<str> = p.name().into();
-                                keywords.into_iter().map(move |keyword| QueryEntry {
-                                    data: serde_json::to_string(&EntryData::DocSet {
-                                        provider: provider_name.clone(),
-                                        id: id.clone(),
-                                    }).unwrap().into(),
-                                    display_text: format!("Type \"{keyword} keyword\" to search {name}").into(),
-                                    icon_name: icon.clone(),
-                                    match_type: MATCH_TYPE_COMPLETION,
-                                    relevance: 1.0,
-                                    properties: Default::default(),
-                                })
-                            },
-                        )
-                        .collect();
-                }
+        if self.debouncer.latest.load(Ordering::SeqCst) == generation {
+            let rs = self.matches(query, QueryMode::Normal).await;
+            self.debouncer.publish(generation, Arc::new(rs.clone()));
+            return Ok(rs);
+        }
 
-                match search_in_doc_sets(p.clone(), doc_sets, query).await {
-                    Ok(v) => v,
-                    Err(e) => {
-                        log::error!("Error searching in doc {}: {e:?}", p.name());
-                        vec![]
-                    }
+        // A newer query superseded this one, answer with its results once they are ready
+        debug_throttled!("Query {query} superseded, waiting for the newest results");
+        loop {
+            let newest = {
+                let published = results.borrow_and_update();
+                match published.0 >= self.debouncer.latest.load(Ordering::SeqCst) {
+                    true => Some(published.1.clone()),
+                    false => None,
                 }
-            });
+            };
+            if let Some(rs) = newest {
+                return Ok(rs.as_ref().clone());
+            }
+            if results.changed().await.is_err() {
+                return Ok(vec![]);
+            }
         }
-
-        collect_join_set(task_set, |rs, buf| {
-            buf.extend(rs?);
-            Ok(())
-        }).await.map_err(|e| Error::Failed(e.to_string()))
     }
 
-    async fn config(&self) -> VariantMap {
+    async fn config(&self) -> VariantMap<'_> {
         log::debug!("Get config");
         hashmap! {
-            "MinLetterCount" => "1".into(),
+            "MinLetterCount" => self.config.search.min_letters.to_string().into(),
         }
     }
 
+    /// Secondary actions offered on every match, as (id, text, icon name).
+    async fn actions(&self) -> Vec<(String, String, String)> {
+        vec![
+            (
+                ACTION_REVEAL_FILE.to_string(),
+                "Show file in file manager".to_string(),
+                "document-open-folder".to_string(),
+            ),
+            (
+                ACTION_COPY_IMPORT.to_string(),
+                "Copy import statement".to_string(),
+                "edit-copy".to_string(),
+            ),
+            (
+                ACTION_OPEN_SOURCE.to_string(),
+                "Open project page".to_string(),
+                "internet-web-browser".to_string(),
+            ),
+        ]
+    }
+
     async fn run(&self, data: &str, action_id: &str) -> Result<()> {
+        self.activity.notify_one();
         let data: EntryData = serde_json::from_str(data)
-            .context("Parsing entry data")
-            .map_err(|e| Error::Failed(format!("{e:?}")))?;
+            .map_err(|source| DocError::Parse { what: "entry data", source })?;
         log::debug!("Run {data:?} with {action_id}");
 
-        if let EntryData::Entry { provider, doc_set_id, url } = data {
-            if let Some(provider) = self.providers.iter().find(|p| p.name() == provider.as_ref()) {
-                provider.open(doc_set_id.as_ref(), url.as_ref()).await
-                    .map_err(|e| Error::Failed(format!("{e:?}")))?;
+        match data {
+            EntryData::More { query, offset } => {
+                // KRunner can't be told to query again, the next time it does gets this page
+                *self.page.lock().unwrap() = Page { query: query.to_string(), offset };
+            }
+            EntryData::Entry { provider, doc_set_id, .. } if action_id == ACTION_OPEN_SOURCE => {
+                let provider = self.providers.require(&provider)?;
+                let homepage = provider.doc_sets().await
+                    .map_err(DocError::from)?
+                    .into_iter()
+                    .find(|ds| ds.id == doc_set_id)
+                    .and_then(|ds| ds.homepage)
+                    .ok_or_else(|| Error::Failed(format!("Doc set {doc_set_id} has no project page")))?;
+                opener::open(&homepage)
+                    .map_err(|source| DocError::OpenFailed { target: homepage.to_string(), source })?;
+            }
+            EntryData::Entry { provider, doc_set_id, title, .. } if action_id == ACTION_COPY_IMPORT => {
+                let provider = self.providers.require(&provider)?;
+                let doc_set = provider.doc_sets().await
+                    .map_err(DocError::from)?
+                    .into_iter()
+                    .find(|ds| ds.id == doc_set_id);
+                let template = doc_set
+                    .and_then(|ds| {
+                        let templates = &self.config.import_templates;
+                        templates.get(ds.name.as_ref()).or_else(|| templates.get(ds.description.as_ref()))
+                    })
+                    .map(String::as_str);
+                opener::copy_to_clipboard(&imports::import_snippet(template, &title)).await
+                    .map_err(DocError::from)?;
+            }
+            EntryData::Entry { provider, doc_set_id, url, .. } if action_id == ACTION_REVEAL_FILE => {
+                let provider = self.providers.require(&provider)?;
+                let file = provider.entry_path(doc_set_id.as_ref(), url.as_ref()).await
+                    .with_context(|| format!("Resolving the file of {url}"))
+                    .map_err(DocError::from)?;
+                opener::reveal(&file).await
+                    .map_err(|source| DocError::OpenFailed { target: file.display().to_string(), source })?;
+            }
+            EntryData::Entry { provider, doc_set_id, url, .. } => {
+                self.providers.require(&provider)?
+                    .open(doc_set_id.as_ref(), url.as_ref()).await
+                    .map_err(|source| DocError::OpenFailed { target: url.to_string(), source })?;
+                if let Err(e) = self.usage.record(&provider, &doc_set_id).await {
+                    log::warn!("Unable to record the use of {provider}/{doc_set_id}: {e:?}");
+                }
+            }
+            EntryData::DocSet { provider, id } => {
+                self.providers.require(&provider)?
+                    .open_doc_set(id.as_ref()).await
+                    .map_err(|source| DocError::OpenFailed { target: id.to_string(), source })?;
             }
         }
 
         Ok(())
     }
 
+    /// Called when the KRunner window closes. The object lives on and later `Match` calls are
+    /// served as usual, providers only drop what they can bring back lazily.
     async fn teardown(&self) {
         log::debug!("Tear down");
+        clean_up(&self.providers).await;
     }
 }
 
 impl EntryType {
-    fn get_krunner_icon(&self) -> Arc<str> {
+    /// Icon shown next to entries of this type, `overrides` maps type names to icon names.
+    pub(crate) fn get_krunner_icon(&self, overrides: &HashMap<String, String>) -> Arc<str> {
+        if let Some(icon) = overrides.get(self.as_str()) {
+            return icon.as_str().into();
+        }
+
         match self {
             EntryType::Class => Arc::from("class-or-package"),
             EntryType::Method | EntryType::Function => Arc::from("code-function"),
             EntryType::Enum => Arc::from("enum"),
             EntryType::Constant => Arc::from("code-variable"),
-            _ => Arc::from("")
+            EntryType::Option => Arc::from("configure"),
+            EntryType::Guide => Arc::from("text-x-generic"),
+            EntryType::Module => Arc::from("code-context"),
+            EntryType::Struct => Arc::from("code-class"),
+            EntryType::Trait | EntryType::Interface => Arc::from("code-typedef"),
+            EntryType::Field | EntryType::Variable => Arc::from("code-variable"),
+            EntryType::Macro => Arc::from("code-function"),
+            EntryType::Type => Arc::from("code-typedef"),
+            EntryType::Other(_) => Arc::from("code-block"),
         }
     }
 }
 
+/// What every doc set search of a single query shares.
+struct SearchContext {
+    /// The typed keyword, empty in global mode.
+    kw: Arc<str>,
+    query: Arc<str>,
+    options: SearchOptions,
+    config: Arc<Config>,
+    /// The plugin's search permits, a doc set search holds one while it runs.
+    permits: Arc<Semaphore>,
+    usage: Arc<Usage>,
+    /// When providers stop waiting for their doc sets, just ahead of the query's own deadline
+    /// so their batch still makes it.
+    provider_deadline: Instant,
+    /// Entries a provider hands back, raised by the page offset so later pages have some left.
+    provider_limit: usize,
+}
+
+const PROVIDER_DEADLINE_MARGIN: Duration = Duration::from_millis(5);
+
+/// Searches `doc_sets` of one provider and hands back its best `provider_limit` entries
+/// in a single batch, returning how many entries were found before truncating.
 async fn search_in_doc_sets(
-    doc_provider: Arc<dyn DocProvider + Send + Sync + 'static>,
+    doc_provider: DynProvider,
+    provider_name: Arc<str>,
     doc_sets: Vec<DocSet>,
-    q: Arc<str>,
-) -> anyhow::Result<Vec<QueryEntry>> {
-    log::debug!("Search {q} in doc sets: {doc_sets:?}");
+    ctx: Arc<SearchContext>,
+    tx: UnboundedSender<Vec<Ranked>>,
+) -> usize {
+    debug_throttled!("Search {} in doc sets: {doc_sets:?}", ctx.query);
+    let (doc_set_tx, mut doc_set_rx) = unbounded_channel();
     let mut join_set = JoinSet::new();
     for ds in doc_sets {
         let doc_provider = doc_provider.clone();
-        let q = q.clone();
+        let provider_name = provider_name.clone();
+        let ctx = ctx.clone();
+        let tx = doc_set_tx.clone();
+        let weight = doc_set_weight(&ds, &ctx.kw);
         join_set.spawn(async move {
-            doc_provider.search(&ds.id, q.as_ref()).await
-                .map(move |entries| entries.into_iter().map(move |SearchEntry { entry_type, title, desc, id: url, relevance }| QueryEntry {
-                    data: serde_json::to_string(&EntryData::Entry { provider: doc_provider.name().into(), doc_set_id: ds.id.clone(), url }).unwrap().into(),
-                    display_text: title,
-                    icon_name: entry_type.get_krunner_icon(),
-                    match_type: MATCH_TYPE_EXACT,
-                    relevance: (relevance as f64) / 100.0,
+            let _permit = match ctx.permits.clone().acquire_owned().await {
+                Ok(v) => v,
+                Err(_) => return,
+            };
+            let mut entries = match doc_provider.search(&ds.id, ctx.query.as_ref(), &ctx.options).await {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("Error searching doc set {} of {}: {e:?}", ds.id, doc_provider.name());
+                    return;
+                }
+            };
+            entries.retain(|e| e.relevance >= ctx.config.search.min_relevance && !ctx.options.hides(e.entry_type.as_str()));
+            // Stands in for doc sets without an icon of their own
+            let provider_icon: Arc<str> = doc_provider.icon().into();
+            let titles = display_titles(ctx.config.trim_prefixes.get(ds.name.as_ref()), &entries);
+            let _ = tx.send(entries.into_iter().zip(titles).map(move |(SearchEntry { entry_type, title, desc, id: url, relevance }, display_text)| {
+                // A trimmed title keeps the full name visible underneath
+                let subtext = match display_text == title {
+                    true => desc.to_string(),
+                    false => title.to_string(),
+                };
+                let (display_text, subtext) = highlight(ctx.config.highlight_mode, display_text, subtext, &ctx.query, &ctx.options);
+                let entry = QueryEntry {
+                    data: serde_json::to_string(&EntryData::Entry {
+                        provider: provider_name.clone(),
+                        doc_set_id: ds.id.clone(),
+                        url,
+                        title: title.clone(),
+                        entry_type: entry_type.clone(),
+                    }).unwrap().into(),
                     properties: hashmap! {
-                        QueryPropertyField::Category => ds.name.to_string().into(),
-                        QueryPropertyField::Subtext => desc.to_string().into(),
+                        QueryPropertyField::Category => match ctx.config.group_by {
+                            GroupBy::DocSet => ds.name.to_string().into(),
+                            GroupBy::Type => entry_type.as_str().to_string().into(),
+                        },
+                        QueryPropertyField::Subtext => subtext.into(),
                         // QueryPropertyField::Urls => vec![url.to_string()].into(),
+                        QueryPropertyField::Actions => entry_actions(&ds).into(),
                     },
-                }))
+                    display_text,
+                    icon_name: match ctx.config.entry_icon_mode {
+                        EntryIconMode::Type => entry_type.get_krunner_icon(&ctx.config.icons),
+                        EntryIconMode::DocSet if ds.icon.is_empty() => provider_icon.clone(),
+                        EntryIconMode::DocSet => ds.icon.clone(),
+                        EntryIconMode::Both if ds.icon.is_empty() => entry_type.get_krunner_icon(&ctx.config.icons),
+                        EntryIconMode::Both => ds.icon.clone(),
+                    },
+                    match_type: MATCH_TYPE_EXACT,
+                    relevance: entry_relevance(relevance, weight),
+                };
+                Ranked { doc_set: Some((provider_name.clone(), ds.id.clone())), entry }
+            }).collect());
         });
     }
+    drop(doc_set_tx);
 
-    collect_join_set(join_set, |r, buf| {
-        buf.extend(r??);
-        Ok(())
-    }).await
+    let mut rs = collect_until(ctx.provider_deadline, &mut doc_set_rx, |_| false).await;
+    drop(join_set);
+
+    let count = rs.len();
+    rs.sort_by(|a, b| b.entry.relevance.total_cmp(&a.entry.relevance));
+    if ctx.config.search.interleave {
+        rs = interleave(rs);
+    }
+    rs.truncate(ctx.provider_limit);
+    let _ = tx.send(rs);
+    count
+}
+
+/// Points out where `query` matched `display_text` as `mode` asks, returning the display text
+/// and subtext to show. Titles the query doesn't literally appear in are left alone.
+fn highlight(mode: HighlightMode, display_text: Arc<str>, subtext: String, query: &str, options: &SearchOptions) -> (Arc<str>, String) {
+    let range = match mode {
+        HighlightMode::None => None,
+        HighlightMode::Markup | HighlightMode::Subtext => match_range(&display_text, query, options),
+    };
+    let range = match (range, mode) {
+        (Some(v), _) => v,
+        // KRunner renders markup titles as rich text whether or not they're highlighted
+        (None, HighlightMode::Markup) => return (escape_markup(&display_text).into(), subtext),
+        (None, _) => return (display_text, subtext),
+    };
+
+    match mode {
+        HighlightMode::Markup => {
+            let text = format!(
+                "{}<b>{}</b>{}",
+                escape_markup(&display_text[..range.start]),
+                escape_markup(&display_text[range.clone()]),
+                escape_markup(&display_text[range.end..]),
+            );
+            (text.into(), subtext)
+        }
+        _ => {
+            let matched = &display_text[range];
+            let subtext = match subtext.is_empty() {
+                true => format!("matched: {matched}"),
+                false => format!("{subtext} · matched: {matched}"),
+            };
+            (display_text, subtext)
+        }
+    }
+}
+
+/// Byte range of the first occurrence of `query` in `text`, compared the way the search folded
+/// them. `None` when folding changed the length of the text, the offsets wouldn't line up.
+fn match_range(text: &str, query: &str, options: &SearchOptions) -> Option<Range<usize>> {
+    let folded = options.fold(text);
+    let query = options.fold(query.trim());
+    if query.is_empty() || folded.len() != text.len() {
+        return None;
+    }
+    let start = folded.find(query.as_ref())?;
+    let range = start..start + query.len();
+    text.get(range.clone()).map(|_| range)
+}
+
+fn escape_markup(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// The friendly title of a doc set ("Python 3") when the provider has one, its name otherwise.
+fn completion_title(doc_set: &DocSet) -> Arc<str> {
+    match doc_set.description.is_empty() {
+        true => doc_set.name.clone(),
+        false => doc_set.description.clone(),
+    }
+}
+
+/// The secondary actions that apply to entries of `doc_set`.
+fn entry_actions(doc_set: &DocSet) -> Vec<String> {
+    let mut actions = vec![ACTION_REVEAL_FILE.to_string(), ACTION_COPY_IMPORT.to_string()];
+    if doc_set.homepage.is_some() {
+        actions.push(ACTION_OPEN_SOURCE.to_string());
+    }
+    actions
 }
 
-async fn collect_join_set<S: 'static, T>(mut js: JoinSet<S>, map: impl Fn(std::result::Result<S, JoinError>, &mut Vec<T>) -> anyhow::Result<()>) -> anyhow::Result<Vec<T>> {
+/// The titles of `entries` as displayed, with the doc set's `trim` prefix taken off. A title
+/// that is nothing but the prefix is shown whole.
+fn display_titles(trim: Option<&TrimPrefix>, entries: &[SearchEntry]) -> Vec<Arc<str>> {
+    let prefix = match trim {
+        None => "",
+        Some(TrimPrefix::Prefix(prefix)) => prefix.as_str(),
+        Some(TrimPrefix::Common) => common_namespace(entries.iter().map(|e| e.title.as_ref())),
+    };
+    entries
+        .iter()
+        .map(|e| match e.title.strip_prefix(prefix) {
+            Some(rest) if !prefix.is_empty() && !rest.is_empty() => rest.into(),
+            _ => e.title.clone(),
+        })
+        .collect()
+}
+
+/// The longest prefix shared by all `titles` that ends with a namespace separator (`.`, `::`
+/// or `/`), so trimming it never cuts a name in half.
+fn common_namespace<'a>(mut titles: impl Iterator<Item = &'a str>) -> &'a str {
+    let first = match titles.next() {
+        Some(v) => v,
+        None => return "",
+    };
+    let shared = titles.fold(first.len(), |len, t| {
+        first.bytes().zip(t.bytes()).take_while(|(a, b)| a == b).count().min(len)
+    });
+    match first.as_bytes()[..shared].iter().rposition(|b| matches!(b, b'.' | b':' | b'/')) {
+        Some(end) => &first[..=end],
+        None => "",
+    }
+}
+
+/// Takes entries round-robin across doc sets: the best entry of every doc set, then their second
+/// best and so on, so a doc set with many strong matches can't crowd the others out. `rs` must
+/// be sorted, its order is kept within each doc set and between doc sets of a round.
+fn interleave(rs: Vec<Ranked>) -> Vec<Ranked> {
+    let mut groups: Vec<VecDeque<Ranked>> = vec![];
+    let mut group_of: HashMap<Option<DocSetKey>, usize> = HashMap::new();
+    for r in rs {
+        let i = *group_of.entry(r.doc_set.clone()).or_insert_with(|| {
+            groups.push(VecDeque::new());
+            groups.len() - 1
+        });
+        groups[i].push_back(r);
+    }
+
+    let mut interleaved = Vec::with_capacity(groups.iter().map(VecDeque::len).sum());
+    while groups.iter().any(|g| !g.is_empty()) {
+        interleaved.extend(groups.iter_mut().filter_map(VecDeque::pop_front));
+    }
+    interleaved
+}
+
+/// Gathers batches from `rx` until every sender is gone, `deadline` passes or what was gathered
+/// is `enough`, whichever comes first.
+async fn collect_until<T>(deadline: Instant, rx: &mut UnboundedReceiver<Vec<T>>, enough: impl Fn(&[T]) -> bool) -> Vec<T> {
     let mut rs = vec![];
-    while let Some(s) = js.join_next().await {
-        map(s, &mut rs)?;
+    loop {
+        match timeout_at(deadline, rx.recv()).await {
+            Ok(Some(batch)) => {
+                rs.extend(batch);
+                if enough(&rs) {
+                    debug_throttled!("Collected enough results, not waiting for the remaining providers");
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(_) => {
+                debug_throttled!("Soft deadline reached, returning {} results collected so far", rs.len());
+                break;
+            }
+        }
+    }
+    rs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use async_trait::async_trait;
+
+    use crate::focus::FocusSource;
+    use crate::provider::DocProvider;
+
+    use super::*;
+
+    /// A provider answering with scripted doc sets and entries, optionally failing or taking
+    /// its time.
+    #[derive(Default)]
+    struct MockProvider {
+        name: &'static str,
+        doc_sets: Vec<DocSet>,
+        /// Entries of each doc set by id, filtered by the query like a name search would.
+        entries: HashMap<&'static str, Vec<SearchEntry>>,
+        /// Every search fails.
+        fail: bool,
+        /// How long every search takes.
+        delay: Duration,
+        /// How many doc set searches ran.
+        searches: AtomicUsize,
+        /// How many doc set searches are running, and the most that ever ran at once.
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        /// How many times doc sets were looked up by keyword.
+        keyword_searches: AtomicUsize,
+        /// How many times the provider was cleaned up.
+        clean_ups: AtomicUsize,
+    }
+
+    impl MockProvider {
+        fn new(name: &'static str) -> Self {
+            Self { name, ..Default::default() }
+        }
+
+        /// Adds a doc set triggered by `keyword`, holding `entries` of (title, relevance).
+        fn doc_set(mut self, id: &'static str, keyword: &str, entries: &[(&str, usize)]) -> Self {
+            self.doc_sets.push(DocSet {
+                id: id.into(),
+                keywords: vec![keyword.into()],
+                name: id.into(),
+                description: Arc::from(""),
+                icon: Arc::from(""),
+                homepage: None,
+            });
+            self.entries.insert(id, entries.iter().map(|(title, relevance)| entry(title, *relevance)).collect());
+            self
+        }
+
+        fn failing(mut self) -> Self {
+            self.fail = true;
+            self
+        }
+
+        fn slow(mut self, delay: Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+    }
+
+    fn entry(title: &str, relevance: usize) -> SearchEntry {
+        SearchEntry {
+            entry_type: EntryType::Function,
+            title: title.into(),
+            desc: Arc::from(""),
+            id: format!("{title}.html").into(),
+            relevance,
+        }
+    }
+
+    #[async_trait]
+    impl DocProvider for MockProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>> {
+            Ok(self.doc_sets.clone())
+        }
+
+        async fn search_doc_sets(&self, keyword: &str) -> anyhow::Result<Vec<DocSet>> {
+            self.keyword_searches.fetch_add(1, Ordering::SeqCst);
+            Ok(self.doc_sets.iter().filter(|ds| ds.keyword_relevance(keyword).is_some()).cloned().collect())
+        }
+
+        fn supports_keyword(&self, keyword: &str) -> bool {
+            self.doc_sets.iter().any(|ds| ds.keyword_relevance(keyword).is_some())
+        }
+
+        async fn search(&self, doc_set_id: &str, q: &str, options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>> {
+            self.searches.fetch_add(1, Ordering::SeqCst);
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            sleep(self.delay).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            anyhow::ensure!(!self.fail, "{} failed on purpose", self.name);
+            let q = q.to_lowercase();
+            Ok(self.entries
+                .get(doc_set_id)
+                .into_iter()
+                .flatten()
+                .filter(|e| e.title.to_lowercase().contains(&q))
+                .take(options.limit)
+                .cloned()
+                .collect())
+        }
+
+        async fn open(&self, _doc_set_id: &str, _entry_url: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn clean_up(&self) -> anyhow::Result<()> {
+            self.clean_ups.fetch_add(1, Ordering::SeqCst);
+            anyhow::ensure!(!self.fail, "{} failed on purpose", self.name);
+            Ok(())
+        }
+    }
+
+    fn plugin(providers: Vec<MockProvider>) -> KRunnerPlugin {
+        plugin_with(providers, Config::default())
+    }
+
+    fn plugin_with(providers: Vec<MockProvider>, config: Config) -> KRunnerPlugin {
+        let providers = providers.into_iter().map(|p| Arc::new(p) as DynProvider).collect();
+        KRunnerPlugin::new(providers, Arc::new(config)).unwrap()
+    }
+
+    fn titles(rs: &[QueryEntry]) -> Vec<&str> {
+        rs.iter().map(|e| e.display_text.as_ref()).collect()
+    }
+
+    #[test]
+    fn refuses_providers_sharing_a_name() {
+        let providers = ["Dash", "tldr", "Dash", "tldr", "man"]
+            .map(|name| Arc::new(MockProvider::new(name)) as DynProvider)
+            .to_vec();
+        let e = KRunnerPlugin::new(providers, Arc::new(Config::default())).err().unwrap();
+        assert_eq!(e.to_string(), r#"Providers with duplicate names: ["Dash", "tldr"]"#);
+    }
+
+    #[test]
+    fn double_equals_asks_for_a_case_exact_match() {
+        assert_eq!(split_query("rust ==String"), Some(("rust", "String", true)));
+        assert_eq!(split_query("rust String"), Some(("rust", "String", false)));
+        assert_eq!(split_term("==\"Into Iter\""), ("Into Iter", true));
+    }
+
+    #[test]
+    fn quoted_phrases_keep_their_spaces() {
+        assert_eq!(split_query("rust \"into iter\" more"), Some(("rust", "into iter", false)));
+        assert_eq!(split_query("rust into iter"), Some(("rust", "into", false)));
+        // A quote that is never closed is searched literally
+        assert_eq!(split_query("rust \"into iter"), Some(("rust", "\"into", false)));
+        assert_eq!(split_term("\"\""), ("", false));
+    }
+
+    #[tokio::test]
+    async fn searches_quoted_phrases_whole() {
+        let plugin = plugin(vec![MockProvider::new("Dash")
+            .doc_set("rust", "rust", &[("IntoIterator", 100), ("Into iter adapters", 80)])]);
+
+        let rs = plugin.matches("rust \"into iter\"", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Into iter adapters"]);
+    }
+
+    #[test]
+    fn markup_highlighting_escapes_every_title() {
+        let options = SearchOptions::default();
+        let (text, _) = highlight(HighlightMode::Markup, "Vec<T>".into(), String::new(), "vec", &options);
+        assert_eq!(text.as_ref(), "<b>Vec</b>&lt;T&gt;");
+        let (text, _) = highlight(HighlightMode::Markup, "Option<&T>".into(), String::new(), "vec", &options);
+        assert_eq!(text.as_ref(), "Option&lt;&amp;T&gt;");
+        let (text, _) = highlight(HighlightMode::None, "Vec<T>".into(), String::new(), "vec", &options);
+        assert_eq!(text.as_ref(), "Vec<T>");
+    }
+
+    #[test]
+    fn subtext_highlighting_names_the_match_below_the_title() {
+        let options = SearchOptions::default();
+        let (text, subtext) = highlight(HighlightMode::Subtext, "Vec<T>".into(), String::new(), "vec", &options);
+        assert_eq!((text.as_ref(), subtext.as_str()), ("Vec<T>", "matched: Vec"));
+        let (_, subtext) = highlight(HighlightMode::Subtext, "IntoIter".into(), "std::vec".into(), "iter", &options);
+        assert_eq!(subtext, "std::vec · matched: Iter");
+        let (_, subtext) = highlight(HighlightMode::Subtext, "Slice".into(), "std".into(), "vec", &options);
+        assert_eq!(subtext, "std");
+    }
+
+    #[test]
+    fn no_highlighting_leaves_title_and_subtext_alone() {
+        let options = SearchOptions::default();
+        let (text, subtext) = highlight(HighlightMode::None, "Vec<T>".into(), "std::vec".into(), "vec", &options);
+        assert_eq!((text.as_ref(), subtext.as_str()), ("Vec<T>", "std::vec"));
+    }
+
+    #[test]
+    fn entry_icons_fall_back_to_a_generic_one_and_can_be_overridden() {
+        let defaults = HashMap::new();
+        for entry_type in [EntryType::Option, EntryType::Guide, EntryType::Module, EntryType::Other("Keyword".into())] {
+            assert!(!entry_type.get_krunner_icon(&defaults).is_empty(), "{entry_type:?}");
+        }
+        let overrides = HashMap::from([("Keyword".to_string(), "edit-find".to_string())]);
+        assert_eq!(EntryType::Other("Keyword".into()).get_krunner_icon(&overrides).as_ref(), "edit-find");
+        assert_eq!(EntryType::Class.get_krunner_icon(&overrides).as_ref(), "class-or-package");
+    }
+
+    #[tokio::test]
+    async fn entry_icons_follow_the_icon_mode() {
+        let icons = |entry_icon_mode: EntryIconMode| async move {
+            let mut provider = MockProvider::new("Dash")
+                .doc_set("rust", "rust", &[("Vec", 100)])
+                .doc_set("python", "python", &[("vec", 90)]);
+            provider.doc_sets[0].icon = Arc::from("rust-gear");
+            let plugin = plugin_with(vec![provider], Config { entry_icon_mode, ..Config::default() });
+            let rust = plugin.matches("rust vec", QueryMode::Normal).await;
+            let python = plugin.matches("python vec", QueryMode::Normal).await;
+            (rust[0].icon_name.to_string(), python[0].icon_name.to_string())
+        };
+
+        let type_icon = EntryType::Function.get_krunner_icon(&HashMap::new()).to_string();
+        assert_eq!(icons(EntryIconMode::Type).await, (type_icon.clone(), type_icon.clone()));
+        assert_eq!(icons(EntryIconMode::DocSet).await, ("rust-gear".into(), "documentation".into()));
+        assert_eq!(icons(EntryIconMode::Both).await, ("rust-gear".into(), type_icon));
+    }
+
+    #[test]
+    fn common_namespace_ends_at_a_separator() {
+        assert_eq!(common_namespace(["android.widget.Button", "android.widget.TextView"].into_iter()), "android.widget.");
+        assert_eq!(common_namespace(["std::vec::Vec", "std::vec::IntoIter"].into_iter()), "std::vec::");
+        assert_eq!(common_namespace(["android.Button", "android.ButtonBar"].into_iter()), "android.");
+        assert_eq!(common_namespace(["Button", "ButtonBar"].into_iter()), "");
+        assert_eq!(common_namespace(std::iter::empty()), "");
+    }
+
+    #[test]
+    fn trims_a_configured_prefix_off_titles_starting_with_it() {
+        let entries = [entry("android.widget.Button", 100), entry("java.lang.String", 80), entry("android.widget.", 50)];
+        let trim = TrimPrefix::Prefix("android.widget.".into());
+        assert_eq!(display_titles(Some(&trim), &entries), vec![
+            Arc::from("Button"),
+            Arc::from("java.lang.String"),
+            Arc::from("android.widget."),
+        ]);
+        assert_eq!(display_titles(None, &entries)[0].as_ref(), "android.widget.Button");
+    }
+
+    #[tokio::test]
+    async fn trimmed_titles_keep_the_full_name_underneath() {
+        let mut config = Config::default();
+        config.trim_prefixes.insert("rust".into(), TrimPrefix::Common);
+        let plugin = plugin_with(vec![MockProvider::new("Dash")
+            .doc_set("rust", "rust", &[("std::vec::Vec", 100), ("std::vec::VecDeque", 80)])], config);
+
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec", "VecDeque"]);
+        assert_eq!(rs[0].to_json()["subtext"], "std::vec::Vec");
+        match serde_json::from_str::<EntryData>(&rs[0].data).unwrap() {
+            EntryData::Entry { title, url, .. } => {
+                assert_eq!(title.as_ref(), "std::vec::Vec");
+                assert_eq!(url.as_ref(), "std::vec::Vec.html");
+            }
+            other => panic!("Unexpected entry data {other:?}"),
+        }
+    }
+
+    #[test]
+    fn sanitizes_control_characters_and_long_queries() {
+        assert_eq!(sanitize_query("rust\tvec\n", 128).as_deref(), Some("rust vec "));
+        assert_eq!(sanitize_query(&"x".repeat(129), 128), None);
+        // Surrounding whitespace doesn't count towards the length
+        assert_eq!(sanitize_query(&format!("  {}  ", "x".repeat(128)), 128).map(|q| q.len()), Some(132));
+        assert!(sanitize_query(&"x".repeat(1000), 0).is_some());
+    }
+
+    #[tokio::test]
+    async fn rejects_queries_longer_than_the_limit() {
+        let mut config = Config::default();
+        config.search.max_query_len = 10;
+        let plugin = plugin_with(vec![MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 100)])], config);
+
+        assert_eq!(titles(&plugin.matches("rust\nvec", QueryMode::Normal).await), vec!["Vec"]);
+        assert!(plugin.matches("rust vec and then some", QueryMode::Normal).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn searches_the_doc_sets_of_the_keyword_best_first() {
+        let plugin = plugin(vec![MockProvider::new("Dash")
+            .doc_set("rust", "rust", &[("BitVec", 70), ("Vec", 100), ("String", 100)])
+            .doc_set("python", "python", &[("vec", 100)])]);
+
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec", "BitVec"]);
+        assert!(rs.iter().all(|e| e.match_type == MATCH_TYPE_EXACT));
+        assert!(rs[0].relevance > rs[1].relevance);
+    }
+
+    #[tokio::test]
+    async fn doc_sets_named_exactly_by_the_keyword_rank_first() {
+        let plugin = plugin(vec![MockProvider::new("Dash")
+            .doc_set("rustembedded", "rustembedded", &[("heapless::Vec", 100)])
+            .doc_set("rust", "rust", &[("Vec", 90)])]);
+
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec", "heapless::Vec"]);
+        assert!(rs[0].relevance > rs[1].relevance);
+    }
+
+    #[tokio::test]
+    async fn merges_providers_by_relevance() {
+        let plugin = plugin(vec![
+            MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 70)]),
+            MockProvider::new("Other").doc_set("rust-std", "rust", &[("Vector", 100)]),
+        ]);
+
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vector", "Vec"]);
+    }
+
+    #[tokio::test]
+    async fn caps_entries_per_doc_set_and_overall() {
+        let many: Vec<(String, usize)> = (0..10).map(|i| (format!("Vec{i}"), 100 - i)).collect();
+        let many: Vec<(&str, usize)> = many.iter().map(|(title, relevance)| (title.as_str(), *relevance)).collect();
+        let mut config = Config::default();
+        config.search.per_docset_limit = 3;
+        config.search.global_limit = 5;
+        let plugin = plugin_with(vec![MockProvider::new("Dash")
+            .doc_set("rust", "rust", &many)
+            .doc_set("rust-std", "rust", &many)], config);
+
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(rs.len(), 5);
+        for doc_set in ["rust", "rust-std"] {
+            let count = rs
+                .iter()
+                .filter(|e| matches!(
+                    serde_json::from_str::<EntryData>(&e.data),
+                    Ok(EntryData::Entry { doc_set_id, .. }) if doc_set_id.as_ref() == doc_set
+                ))
+                .count();
+            assert!(count <= 3, "{doc_set} contributed {count} entries");
+        }
+    }
+
+    #[tokio::test]
+    async fn interleaves_doc_sets_whatever_the_grouping() {
+        let mut config = Config::default();
+        config.search.interleave = true;
+        config.group_by = GroupBy::Type;
+        let plugin = plugin_with(vec![MockProvider::new("Dash")
+            .doc_set("rust", "rust", &[("Vec", 100), ("VecDeque", 90)])
+            .doc_set("rust-std", "rust", &[("Vector", 80)])], config);
+
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec", "Vector", "VecDeque"]);
+    }
+
+    #[tokio::test]
+    async fn categorizes_entries_as_group_by_asks() {
+        let provider = || vec![MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 100)])];
+        for (group_by, category) in [(GroupBy::DocSet, "rust"), (GroupBy::Type, "Function")] {
+            let config = Config { group_by, ..Config::default() };
+            let rs = plugin_with(provider(), config).matches("rust vec", QueryMode::Normal).await;
+            assert_eq!(rs[0].properties[&QueryPropertyField::Category], Value::from(category));
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_go_past_the_per_doc_set_limit() {
+        let many: Vec<(String, usize)> = (0..6).map(|i| (format!("Vec{i}"), 100 - i)).collect();
+        let many: Vec<(&str, usize)> = many.iter().map(|(title, relevance)| (title.as_str(), *relevance)).collect();
+        let mut config = Config::default();
+        config.search.paginate = true;
+        config.search.per_docset_limit = 3;
+        config.search.global_limit = 2;
+        let plugin = plugin_with(vec![MockProvider::new("Dash").doc_set("rust", "rust", &many)], config);
+
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec0", "Vec1", "Show more results for \"vec\""]);
+        *plugin.page.lock().unwrap() = Page { query: "rust vec".into(), offset: 2 };
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec2", "Vec3", "Show more results for \"vec\""]);
+        *plugin.page.lock().unwrap() = Page { query: "rust vec".into(), offset: 4 };
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec4", "Vec5"]);
+    }
+
+    #[tokio::test]
+    async fn pages_reset_when_the_query_changes() {
+        let mut config = Config::default();
+        config.search.paginate = true;
+        config.search.global_limit = 1;
+        let plugin = plugin_with(vec![MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec0", 100), ("Vec1", 90)])], config);
+
+        *plugin.page.lock().unwrap() = Page { query: "rust vec".into(), offset: 1 };
+        let rs = plugin.matches("rust vec0", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec0"]);
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs)[0], "Vec0");
+    }
+
+    #[tokio::test]
+    async fn debounces_bursts_of_queries_into_the_newest() {
+        let provider = Arc::new(MockProvider::new("Dash").doc_set("rust", "rust", &[("Slice", 100), ("Vec", 100)]));
+        let mut config = Config::default();
+        config.search.debounce_ms = 80;
+        let plugin = KRunnerPlugin::new(vec![provider.clone() as DynProvider], Arc::new(config)).unwrap();
+        let plugin = &plugin;
+        let typed = |delay: u64, query: &'static str| async move {
+            sleep(Duration::from_millis(delay)).await;
+            plugin.query(query).await.unwrap()
+        };
+
+        let (first, second, third) = tokio::join!(typed(0, "rust s"), typed(10, "rust sl"), typed(20, "rust vec"));
+        assert_eq!(titles(&third), vec!["Vec"]);
+        assert_eq!(titles(&first), titles(&third));
+        assert_eq!(titles(&second), titles(&third));
+        assert_eq!(provider.searches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn skips_providers_that_dont_support_the_keyword() {
+        let dash = Arc::new(MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 100)]));
+        let man = Arc::new(MockProvider::new("man").doc_set("man", "man", &[("vec", 100)]));
+        let providers = vec![dash.clone() as DynProvider, man.clone() as DynProvider];
+        let plugin = KRunnerPlugin::new(providers, Arc::new(Config::default())).unwrap();
+
+        let rs = plugin.query("rust vec").await.unwrap();
+        assert_eq!(titles(&rs), vec!["Vec"]);
+        assert_eq!(man.keyword_searches.load(Ordering::SeqCst), 0);
+        assert_eq!(man.searches.load(Ordering::SeqCst), 0);
+
+        let rs = plugin.query("man vec").await.unwrap();
+        assert_eq!(titles(&rs), vec!["vec"]);
+        assert_eq!(dash.keyword_searches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn overlapping_queries_share_the_search_bound() {
+        const DOC_SETS: [&str; 10] = ["d0", "d1", "d2", "d3", "d4", "d5", "d6", "d7", "d8", "d9"];
+        let mut provider = MockProvider::new("Dash").slow(Duration::from_millis(20));
+        for id in DOC_SETS {
+            provider = provider.doc_set(id, "rust", &[("Vec", 100)]);
+        }
+        let provider = Arc::new(provider);
+        let mut config = Config::default();
+        config.search.max_concurrent_searches = 3;
+        config.search.timeout_ms = 5_000;
+        let plugin = KRunnerPlugin::new(vec![provider.clone() as DynProvider], Arc::new(config)).unwrap();
+
+        let (a, b, c, d) = tokio::join!(plugin.query("rust v"), plugin.query("rust ve"), plugin.query("rust vec"), plugin.query("rust Vec"));
+        assert!([a, b, c, d].iter().all(Result::is_ok));
+        assert!(provider.searches.load(Ordering::SeqCst) > DOC_SETS.len());
+        assert_eq!(provider.max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn superseded_queries_return_when_the_newest_is_cancelled() {
+        let mut config = Config::default();
+        config.search.debounce_ms = 80;
+        let plugin = plugin_with(vec![MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 100)])], config);
+        let cancelled = async {
+            sleep(Duration::from_millis(10)).await;
+            timeout(Duration::from_millis(20), plugin.query("rust vec")).await
+        };
+
+        let (first, newest) = timeout(Duration::from_secs(1), async { tokio::join!(plugin.query("rust v"), cancelled) })
+            .await
+            .expect("the superseded query never returned");
+        assert!(newest.is_err());
+        assert!(first.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn searches_the_default_keyword_when_the_first_word_is_no_keyword() {
+        let mut config = Config::default();
+        config.search.default_keyword = "rust".into();
+        let provider = Arc::new(MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 100)]));
+        let plugin = KRunnerPlugin::new(vec![provider.clone() as DynProvider], Arc::new(config)).unwrap();
+
+        let rs = plugin.matches("vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec"]);
+        // Telling `vec` isn't a keyword doesn't take a doc set lookup of its own
+        assert_eq!(provider.keyword_searches.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn an_explicit_keyword_takes_precedence_over_the_default() {
+        let mut config = Config::default();
+        config.search.default_keyword = "rust".into();
+        let plugin = plugin_with(vec![MockProvider::new("Dash")
+            .doc_set("rust", "rust", &[("Vec", 100)])
+            .doc_set("python", "python", &[("vec", 90)])], config);
+
+        let rs = plugin.matches("python vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["vec"]);
+    }
+
+    #[tokio::test]
+    async fn completes_keywords_when_no_term_is_typed() {
+        let plugin = plugin(vec![MockProvider::new("Dash")
+            .doc_set("rust", "rust", &[("Vec", 100)])
+            .doc_set("python", "python", &[("vec", 100)])]);
+
+        let rs = plugin.matches("rus", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Type \"rust keyword\" to search rust", "Open rust index"]);
+        assert!(rs.iter().all(|e| e.match_type == MATCH_TYPE_COMPLETION && e.relevance <= COMPLETION_RELEVANCE));
+    }
+
+    #[tokio::test]
+    async fn caps_completions_keeping_the_exact_keyword() {
+        let mut provider = MockProvider::new("Dash");
+        let keywords: Vec<String> = (0..12).map(|i| format!("c{i}")).collect();
+        for keyword in &keywords {
+            provider = provider.doc_set("other", keyword, &[]);
+        }
+        let mut config = Config::default();
+        config.search.completion_limit = 3;
+        let plugin = plugin_with(vec![provider.doc_set("c", "c", &[])], config);
+
+        let rs = plugin.matches("c", QueryMode::Normal).await;
+        assert_eq!(rs.len(), 3);
+        assert_eq!(titles(&rs)[..2], ["Type \"c keyword\" to search c", "Open c index"]);
+    }
+
+    #[tokio::test]
+    async fn completions_list_doc_sets_in_use_first() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let doc_sets = || MockProvider::new("Dash")
+            .doc_set("pyqt", "pyqt", &[])
+            .doc_set("python", "python", &[]);
+        let completed = |rs: &[QueryEntry]| rs.iter().step_by(2).map(|e| e.display_text.to_string()).collect::<Vec<_>>();
+
+        let mut config = Config::default();
+        config.search.sort_completions_by_usage = true;
+        let mut plugin = plugin_with(vec![doc_sets()], config);
+        plugin.usage = Arc::new(Usage::load(Some(dir.path().join("usage.json"))));
+        let unused = plugin.matches("py", QueryMode::Normal).await;
+        assert_eq!(completed(&unused), vec!["Type \"pyqt keyword\" to search pyqt", "Type \"python keyword\" to search python"]);
+
+        plugin.usage.record("Dash", "python").await.unwrap();
+        let used = plugin.matches("py", QueryMode::Normal).await;
+        assert_eq!(completed(&used), vec!["Type \"python keyword\" to search python", "Type \"pyqt keyword\" to search pyqt"]);
+    }
+
+    #[tokio::test]
+    async fn global_prefix_searches_every_doc_set() {
+        let plugin = plugin(vec![
+            MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 100)]),
+            MockProvider::new("Other").doc_set("python", "python", &[("vector", 80)]),
+        ]);
+
+        let rs = plugin.matches("??vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec", "vector"]);
+        assert!(plugin.matches("??", QueryMode::Normal).await.is_empty());
+    }
+
+    /// Reports a fixed window class.
+    struct MockFocus(Option<&'static str>);
+
+    #[async_trait]
+    impl FocusSource for MockFocus {
+        async fn window_class(&self) -> Option<String> {
+            self.0.map(str::to_string)
+        }
+    }
+
+    #[tokio::test]
+    async fn focused_prefix_searches_the_doc_set_of_the_focused_application() {
+        let mut config = Config::default();
+        config.search.focused_prefix = "@@".into();
+        config.window_keywords.insert("kate".into(), "rust".into());
+        let focused = |class| plugin_with(vec![MockProvider::new("Dash")
+            .doc_set("rust", "rust", &[("Vec", 100)])
+            .doc_set("python", "python", &[("vec", 100)])], config.clone())
+            .with_focus(Arc::new(MockFocus(class)));
+
+        assert_eq!(titles(&focused(Some("org.kde.kate")).matches("@@vec", QueryMode::Normal).await), vec!["Vec"]);
+        // Unmapped or unknown applications fall back to searching the query as typed
+        assert!(focused(Some("firefox")).matches("@@vec", QueryMode::Normal).await.is_empty());
+        assert!(focused(None).matches("@@vec", QueryMode::Normal).await.is_empty());
+        assert_eq!(titles(&focused(None).matches("python vec", QueryMode::Normal).await), vec!["vec"]);
+    }
+
+    #[tokio::test]
+    async fn provider_prefix_only_asks_that_provider() {
+        let plugin = plugin(vec![
+            MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 100)]),
+            MockProvider::new("Other").doc_set("rust-std", "rust", &[("Vector", 80)]),
+        ]);
+
+        let rs = plugin.matches("other:rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vector"]);
+        // An unknown prefix is just part of the keyword, which then matches nothing
+        assert!(plugin.matches("nope:rust vec", QueryMode::Normal).await.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cleans_up_once_no_query_arrived_for_the_idle_timeout() {
+        let provider = Arc::new(MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 100)]));
+        let plugin = KRunnerPlugin::new(vec![provider.clone() as DynProvider], Arc::new(Config::default())).unwrap();
+        let idle = tokio::spawn({
+            let activity = plugin.activity.clone();
+            let providers = plugin.providers.clone();
+            async move { clean_up_when_idle(&activity, &providers, Duration::from_secs(60)).await }
+        });
+
+        // Every query starts the timeout over
+        sleep(Duration::from_secs(50)).await;
+        plugin.query("rust vec").await.unwrap();
+        sleep(Duration::from_secs(50)).await;
+        assert_eq!(provider.clean_ups.load(Ordering::SeqCst), 0);
+
+        sleep(Duration::from_secs(11)).await;
+        assert!(idle.is_finished());
+        assert_eq!(provider.clean_ups.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn run_reports_errors_without_a_debug_dump() {
+        let plugin = plugin(vec![MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 100)])]);
+
+        let data = serde_json::to_string(&EntryData::DocSet { provider: "man".into(), id: "ls".into() }).unwrap();
+        match plugin.run(&data, "").await {
+            Err(Error::InvalidArgs(message)) => assert_eq!(message, "Unknown provider man"),
+            other => panic!("expected an unknown provider, got {other:?}"),
+        }
+        match plugin.run("{", "").await {
+            Err(Error::InvalidArgs(message)) => assert!(message.starts_with("Unable to parse the entry data: "), "{message}"),
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn teardown_cleans_up_every_provider_past_a_failing_one() {
+        let failing = Arc::new(MockProvider::new("Dash").failing());
+        let healthy = Arc::new(MockProvider::new("tldr"));
+        let plugin = KRunnerPlugin::new(vec![failing.clone() as DynProvider, healthy.clone() as DynProvider], Arc::new(Config::default())).unwrap();
+
+        plugin.teardown().await;
+        assert_eq!(failing.clean_ups.load(Ordering::SeqCst), 1);
+        assert_eq!(healthy.clean_ups.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn skips_failing_and_slow_providers() {
+        let plugin = plugin(vec![
+            MockProvider::new("Dash").doc_set("rust", "rust", &[("Vec", 100)]),
+            MockProvider::new("Broken").doc_set("rust-broken", "rust", &[("VecBroken", 100)]).failing(),
+            MockProvider::new("Slow").doc_set("rust-slow", "rust", &[("VecSlow", 100)]).slow(Duration::from_secs(5)),
+        ]);
+
+        let rs = plugin.matches("rust vec", QueryMode::Normal).await;
+        assert_eq!(titles(&rs), vec!["Vec"]);
     }
-    Ok(rs)
 }
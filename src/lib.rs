@@ -0,0 +1,23 @@
+pub mod config;
+pub mod control;
+pub mod dash;
+pub mod devdocs;
+pub mod docserver;
+pub mod error;
+pub mod feeds;
+pub mod focus;
+pub mod godoc;
+pub mod httpapi;
+pub mod imports;
+pub mod infopages;
+pub mod krunner;
+pub mod logging;
+pub mod markdown;
+pub mod opener;
+pub mod provider;
+pub mod pydoc;
+pub mod registry;
+pub mod scorers;
+pub mod tldr;
+pub mod usage;
+pub mod watch;
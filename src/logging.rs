@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The log filter to run with: `--log-level` when given, otherwise `RUST_LOG`, falling back to
+/// the config's `log_level`.
+pub fn log_filter<'a>(cli_level: Option<&'a str>, env_level: Option<&'a str>, config_level: &'a str) -> &'a str {
+    cli_level.or(env_level).unwrap_or(config_level)
+}
+
+/// How often a throttled call site may log.
+const THROTTLE_INTERVAL_MS: u64 = 1000;
+
+/// Lets a log line through at most once per [THROTTLE_INTERVAL_MS], counting the ones held back.
+pub struct Throttle {
+    last_ms: AtomicU64,
+    skipped: AtomicU64,
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Throttle {
+    pub const fn new() -> Self {
+        Self {
+            last_ms: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether to log now, with how many lines were skipped since the last one that was logged.
+    pub fn admit(&self) -> Option<u64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        let last = self.last_ms.load(Ordering::Relaxed);
+        let due = now.saturating_sub(last) >= THROTTLE_INTERVAL_MS;
+        match due && self.last_ms.compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            true => Some(self.skipped.swap(0, Ordering::Relaxed)),
+            false => {
+                self.skipped.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+}
+
+/// `log::debug!` for lines issued on every keystroke: each call site logs at most once per
+/// [THROTTLE_INTERVAL_MS] and says how many lines it skipped. Only meant for debug chatter,
+/// warnings and errors are always logged.
+macro_rules! debug_throttled {
+    ($($arg:tt)+) => {{
+        static THROTTLE: $crate::logging::Throttle = $crate::logging::Throttle::new();
+        if log::log_enabled!(log::Level::Debug) {
+            match THROTTLE.admit() {
+                Some(0) => log::debug!($($arg)+),
+                Some(skipped) => log::debug!("{} ({skipped} similar lines skipped)", format_args!($($arg)+)),
+                None => {}
+            }
+        }
+    }};
+}
+
+pub(crate) use debug_throttled;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_filter_prefers_the_cli_then_rust_log_then_the_config() {
+        assert_eq!(log_filter(Some("trace"), Some("debug"), "info"), "trace");
+        assert_eq!(log_filter(None, Some("debug"), "info"), "debug");
+        assert_eq!(log_filter(None, None, "info"), "info");
+    }
+}
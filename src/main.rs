@@ -1,18 +1,89 @@
-use std::future::pending;
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::dash::Dash;
-use crate::krunner::KRunnerPlugin;
-
-mod dash;
-mod krunner;
-mod provider;
+use kdashdoc::config::Config;
+use kdashdoc::feeds::Feeds;
+use kdashdoc::krunner::{KRunnerPlugin, KRunnerPluginBuilder, QueryMode};
+use kdashdoc::logging::log_filter;
+use kdashdoc::registry::{build_providers, log_checks};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    std::env::set_var("RUST_LOG", "debug");
-    env_logger::init();
-    KRunnerPlugin::new(vec![Arc::new(Dash::new_with_default().await?)], "/krunner").await?;
-    pending::<()>().await;
+    let (log_level, args) = take_log_level(std::env::args().skip(1).collect())?;
+    // Nothing is logged until the config has told us how much to log
+    let config = Arc::new(Config::load().await?);
+    init_logging(log_level.as_deref(), &config.log_level);
+    log::debug!("Using config: {config:#?}");
+
+    let providers = build_providers(&config, &[]).await?;
+    if log::log_enabled!(log::Level::Debug) {
+        log_checks(&providers).await;
+    }
+    match args.first().map(String::as_str) {
+        Some("query") => return query(KRunnerPlugin::new(providers, config)?, &args[1..]).await,
+        Some(other) => anyhow::bail!("Unknown command {other:?}\n{USAGE}"),
+        None => {}
+    }
+
+    let feeds = match config.feeds.enabled {
+        true => Some(Feeds::new(config.dash.docset_root()?)),
+        false => None,
+    };
+    let idle_timeout = Duration::from_secs(config.idle_timeout_minutes * 60);
+    KRunnerPluginBuilder::new(config)
+        .providers(providers)
+        .feeds(feeds)
+        .object_path("/krunner")
+        .build()
+        .await?
+        .serve(idle_timeout)
+        .await;
+    Ok(())
+}
+
+const USAGE: &str = "Usage: kdashdoc [--log-level <filter>] [query [--single-runner] <query>...]";
+
+/// Pulls `--log-level <filter>` (or `--log-level=<filter>`) out of the arguments.
+fn take_log_level(args: Vec<String>) -> anyhow::Result<(Option<String>, Vec<String>)> {
+    let mut level = None;
+    let mut rest = vec![];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--log-level" {
+            level = Some(args.next().ok_or_else(|| anyhow::anyhow!("Missing log level\n{USAGE}"))?);
+        } else if let Some(v) = arg.strip_prefix("--log-level=") {
+            level = Some(v.to_string());
+        } else {
+            rest.push(arg);
+        }
+    }
+    Ok((level, rest))
+}
+
+/// Logs with the filter [log_filter] picks.
+fn init_logging(cli_level: Option<&str>, config_level: &str) {
+    let env_level = std::env::var("RUST_LOG").ok();
+    env_logger::Builder::new()
+        .parse_filters(log_filter(cli_level, env_level.as_deref(), config_level))
+        .init();
+}
+
+/// Runs a single query without touching D-Bus and prints its matches to stdout as JSON.
+async fn query(plugin: KRunnerPlugin, args: &[String]) -> anyhow::Result<()> {
+    let (mode, words) = match args.first().map(String::as_str) {
+        Some("--single-runner") => (QueryMode::SingleRunner, &args[1..]),
+        _ => (QueryMode::Normal, args),
+    };
+    if words.is_empty() {
+        anyhow::bail!("Missing query\n{USAGE}");
+    }
+
+    let entries: Vec<_> = plugin
+        .matches(&words.join(" "), mode)
+        .await
+        .iter()
+        .map(|e| e.to_json())
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&entries)?);
     Ok(())
 }
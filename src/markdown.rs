@@ -0,0 +1,359 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use notify::RecommendedWatcher;
+use tokio::task::spawn_blocking;
+
+use crate::config::MarkdownRootConfig;
+use crate::opener;
+use crate::provider::{name_relevance, sort_by_keyword, DocProvider, DocSet, EntryType, SearchEntry, SearchOptions};
+use crate::watch;
+
+/// A searchable file name or heading inside a markdown root.
+#[derive(Debug)]
+struct MarkdownEntry {
+    title: Arc<str>,
+    /// Path relative to the root, with a `#slug` for headings.
+    url: Arc<str>,
+}
+
+/// A configured folder of markdown files, exposed as one doc set.
+#[derive(Debug)]
+struct MarkdownDocSet {
+    id: Arc<str>,
+    name: Arc<str>,
+    keywords: Vec<Arc<str>>,
+    root: PathBuf,
+    entries: Vec<MarkdownEntry>,
+}
+
+impl MarkdownDocSet {
+    fn new(config: &MarkdownRootConfig) -> Self {
+        let name: Arc<str> = config.name
+            .clone()
+            .or_else(|| config.path.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "Markdown".to_string())
+            .into();
+
+        let keywords = match config.keywords.is_empty() {
+            true => vec![name.to_lowercase().into()],
+            false => config.keywords.iter().map(|k| k.to_lowercase().into()).collect(),
+        };
+
+        let mut entries = vec![];
+        index_folder(&config.path, &config.path, &mut entries);
+        log::debug!("Indexed {} markdown entries in {}", entries.len(), config.path.display());
+
+        Self {
+            id: config.path.to_string_lossy().into(),
+            name,
+            keywords,
+            root: config.path.clone(),
+            entries,
+        }
+    }
+
+    fn to_doc_set(&self) -> DocSet {
+        DocSet {
+            id: self.id.clone(),
+            keywords: self.keywords.clone(),
+            name: self.name.clone(),
+            description: self.id.clone(),
+            icon: Arc::from("text-markdown"),
+            homepage: None,
+        }
+    }
+}
+
+fn index_folder(root: &Path, dir: &Path, entries: &mut Vec<MarkdownEntry>) {
+    let dir_entries = match std::fs::read_dir(dir) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("Unable to list {}: {e}", dir.display());
+            return;
+        }
+    };
+
+    for entry in dir_entries.flatten() {
+        let path = entry.path();
+        if path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+
+        // Symlinked folders aren't followed, they could loop back to a parent
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            index_folder(root, &path, entries);
+        } else if path.extension().is_some_and(|ext| ext == "md") {
+            index_file(root, &path, entries);
+        }
+    }
+}
+
+fn index_file(root: &Path, path: &Path, entries: &mut Vec<MarkdownEntry>) {
+    let rel: Arc<str> = match path.strip_prefix(root) {
+        Ok(rel) => rel.to_string_lossy().into(),
+        Err(_) => return,
+    };
+
+    if let Some(stem) = path.file_stem() {
+        entries.push(MarkdownEntry {
+            title: stem.to_string_lossy().into(),
+            url: rel.clone(),
+        });
+    }
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("Unable to read {}: {e}", path.display());
+            return;
+        }
+    };
+
+    let mut in_code_block = false;
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        if let Some(heading) = parse_heading(line) {
+            entries.push(MarkdownEntry {
+                title: heading.into(),
+                url: format!("{rel}#{}", slug(heading)).into(),
+            });
+        }
+    }
+}
+
+/// Returns the text of an ATX heading line (`# Title` to `###### Title`).
+fn parse_heading(line: &str) -> Option<&str> {
+    let text = line.trim_start_matches('#');
+    let level = line.len() - text.len();
+    if !(1..=6).contains(&level) || !text.starts_with(' ') {
+        return None;
+    }
+    Some(text.trim()).filter(|t| !t.is_empty())
+}
+
+/// GitHub style heading anchor.
+fn slug(heading: &str) -> String {
+    heading
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c.to_ascii_lowercase()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The file `entry_url` points to under `root`, refusing URLs that lead out of it.
+async fn resolve_entry(root: &Path, entry_url: &str) -> anyhow::Result<PathBuf> {
+    let path = entry_url.split_once('#').map_or(entry_url, |(path, _)| path);
+    let root = tokio::fs::canonicalize(root).await.with_context(|| format!("Resolving {}", root.display()))?;
+    let file = tokio::fs::canonicalize(root.join(path)).await.with_context(|| format!("Resolving {path}"))?;
+    anyhow::ensure!(file.starts_with(&root), "{path} is outside of {}", root.display());
+    Ok(file)
+}
+
+/// Serves configured folders of markdown files, each folder being a doc set. The index is
+/// rebuilt whenever files under a folder change.
+pub struct Markdown {
+    roots: Arc<Vec<MarkdownRootConfig>>,
+    doc_sets: Arc<RwLock<Vec<MarkdownDocSet>>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl Markdown {
+    pub async fn new(roots: Vec<MarkdownRootConfig>) -> anyhow::Result<Self> {
+        // Missing roots index as empty, watching them would fail the whole provider
+        let paths: Vec<PathBuf> = roots
+            .iter()
+            .map(|r| r.path.clone())
+            .filter(|path| match path.is_dir() {
+                true => true,
+                false => {
+                    log::warn!("Markdown root {} doesn't exist, not watching it", path.display());
+                    false
+                }
+            })
+            .collect();
+        let roots = Arc::new(roots);
+        let doc_sets = Arc::new(RwLock::new(Self::index(roots.clone()).await?));
+
+        let (watcher, mut changes) = watch::watch(&paths)?;
+        let weak = Arc::downgrade(&doc_sets);
+        let watched_roots = roots.clone();
+        tokio::spawn(async move {
+            while changes.recv().await.is_some() {
+                let doc_sets = match weak.upgrade() {
+                    Some(v) => v,
+                    None => break,
+                };
+
+                log::debug!("Markdown files changed, rebuilding index");
+                match Self::index(watched_roots.clone()).await {
+                    Ok(v) => *doc_sets.write().unwrap() = v,
+                    Err(e) => log::error!("Error rebuilding markdown index: {e:?}"),
+                }
+            }
+        });
+
+        Ok(Self {
+            roots,
+            doc_sets,
+            _watcher: watcher,
+        })
+    }
+
+    async fn index(roots: Arc<Vec<MarkdownRootConfig>>) -> anyhow::Result<Vec<MarkdownDocSet>> {
+        spawn_blocking(move || roots.iter().map(MarkdownDocSet::new).collect())
+            .await
+            .context("Indexing markdown roots")
+    }
+}
+
+#[async_trait]
+impl DocProvider for Markdown {
+    fn name(&self) -> &str {
+        "Markdown"
+    }
+
+    fn icon(&self) -> &str {
+        "text-markdown"
+    }
+
+    async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>> {
+        Ok(self.doc_sets.read().unwrap().iter().map(MarkdownDocSet::to_doc_set).collect())
+    }
+
+    fn supports_keyword(&self, keyword: &str) -> bool {
+        let keyword = keyword.trim().to_lowercase();
+        !keyword.is_empty() && self.doc_sets
+            .read()
+            .unwrap()
+            .iter()
+            .any(|ds| ds.keywords.iter().any(|k| k.starts_with(&keyword)))
+    }
+
+    async fn search_doc_sets(&self, keyword: &str) -> anyhow::Result<Vec<DocSet>> {
+        let keyword = keyword.trim().to_lowercase();
+        if keyword.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut rs: Vec<DocSet> = self.doc_sets
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|ds| ds.keywords.iter().any(|k| k.starts_with(&keyword)))
+            .map(MarkdownDocSet::to_doc_set)
+            .collect();
+        sort_by_keyword(&mut rs, &keyword);
+        Ok(rs)
+    }
+
+    async fn search(&self, doc_set_id: &str, q: &str, options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>> {
+        let q = options.fold(q.trim());
+        let doc_sets = self.doc_sets.read().unwrap();
+        let doc_set = match doc_sets.iter().find(|ds| ds.id.as_ref() == doc_set_id) {
+            Some(v) => v,
+            None => return Ok(vec![]),
+        };
+
+        let mut rs: Vec<SearchEntry> = doc_set.entries
+            .iter()
+            .filter_map(|entry| {
+                let relevance = name_relevance(&options.fold(&entry.title), &q)?;
+                Some(SearchEntry {
+                    entry_type: EntryType::Guide,
+                    title: entry.title.clone(),
+                    desc: entry.url.clone(),
+                    id: entry.url.clone(),
+                    relevance,
+                })
+            })
+            .collect();
+
+        rs.sort_by(|a, b| b.relevance.cmp(&a.relevance).then(a.title.len().cmp(&b.title.len())));
+        rs.truncate(options.limit);
+        Ok(rs)
+    }
+
+    async fn open(&self, doc_set_id: &str, entry_url: &str) -> anyhow::Result<()> {
+        let file = self.entry_path(doc_set_id, entry_url).await?;
+        opener::open(&file.to_string_lossy())
+    }
+
+    async fn entry_path(&self, doc_set_id: &str, entry_url: &str) -> anyhow::Result<PathBuf> {
+        let root = self.doc_sets
+            .read()
+            .unwrap()
+            .iter()
+            .find(|ds| ds.id.as_ref() == doc_set_id)
+            .map(|ds| ds.root.clone())
+            .with_context(|| format!("Unknown markdown root {doc_set_id}"))?;
+        resolve_entry(&root, entry_url).await
+    }
+
+    async fn open_doc_set(&self, doc_set_id: &str) -> anyhow::Result<()> {
+        let root = self.doc_sets
+            .read()
+            .unwrap()
+            .iter()
+            .find(|ds| ds.id.as_ref() == doc_set_id)
+            .map(|ds| ds.root.clone())
+            .with_context(|| format!("Unknown markdown root {doc_set_id}"))?;
+        opener::open(&root.to_string_lossy())
+    }
+
+    /// Rebuilds the index right away, for changes the watcher can't see such as a remounted root.
+    async fn reload(&self) -> anyhow::Result<()> {
+        *self.doc_sets.write().unwrap() = Self::index(self.roots.clone()).await?;
+        Ok(())
+    }
+
+    async fn clean_up(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn skips_symlinked_folders() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("guide")).unwrap();
+        std::fs::write(dir.path().join("guide").join("intro.md"), "# Getting started\n").unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("guide").join("loop")).unwrap();
+
+        let mut entries = vec![];
+        index_folder(dir.path(), dir.path(), &mut entries);
+        let urls: Vec<&str> = entries.iter().map(|e| e.url.as_ref()).collect();
+        assert_eq!(urls, vec!["guide/intro.md", "guide/intro.md#getting-started"]);
+    }
+
+    #[tokio::test]
+    async fn resolves_entries_inside_the_root_only() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("notes");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("intro.md"), "").unwrap();
+        std::fs::write(dir.path().join("secret.md"), "").unwrap();
+
+        let file = resolve_entry(&root, "intro.md#getting-started").await.unwrap();
+        assert_eq!(file, root.canonicalize().unwrap().join("intro.md"));
+        assert!(resolve_entry(&root, "../secret.md").await.is_err());
+        assert!(resolve_entry(&root, "sub/../../secret.md").await.is_err());
+    }
+}
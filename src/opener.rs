@@ -0,0 +1,90 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use zbus::Connection;
+
+/// Characters escaped in the path of a `file://` URL. `%` is included because the path has
+/// been decoded already, `#` and `?` because they would end the path early.
+const PATH_ESCAPES: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'%').add(b'<').add(b'>').add(b'?').add(b'`').add(b'{').add(b'}');
+
+/// Hands `target` (a URL or a local path) to the desktop's default application.
+pub fn open(target: &str) -> anyhow::Result<()> {
+    log::debug!("Opening {target}");
+    spawn_detached("xdg-open", &[target])
+}
+
+/// Builds a `file://` URL for `file`, encoding whatever a browser would otherwise misread.
+pub fn file_url(file: &Path) -> String {
+    format!("file://{}", utf8_percent_encode(&file.to_string_lossy(), PATH_ESCAPES))
+}
+
+/// Starts `program` without waiting for it, detached from our stdio.
+pub fn spawn_detached(program: &str, args: &[&str]) -> anyhow::Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Launching {program}"))?;
+
+    // Reap the child in the background so it doesn't linger as a zombie
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Puts `text` on the clipboard through Klipper, or `wl-copy` outside of Plasma.
+pub async fn copy_to_clipboard(text: &str) -> anyhow::Result<()> {
+    let copied = async {
+        Connection::session()
+            .await?
+            .call_method(
+                Some("org.kde.klipper"),
+                "/klipper",
+                Some("org.kde.klipper.klipper"),
+                "setClipboardContents",
+                &(text,),
+            )
+            .await
+    }.await;
+
+    match copied {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            log::debug!("Klipper unavailable, copying with wl-copy: {e:?}");
+            spawn_detached("wl-copy", &["--", text])
+        }
+    }
+}
+
+/// Shows `file` selected in the desktop's file manager, or opens its folder when no
+/// file manager implements `org.freedesktop.FileManager1`.
+pub async fn reveal(file: &Path) -> anyhow::Result<()> {
+    let uri = file_url(file);
+    log::debug!("Revealing {uri}");
+
+    let shown = async {
+        Connection::session()
+            .await?
+            .call_method(
+                Some("org.freedesktop.FileManager1"),
+                "/org/freedesktop/FileManager1",
+                Some("org.freedesktop.FileManager1"),
+                "ShowItems",
+                &(vec![uri.as_str()], ""),
+            )
+            .await
+    }.await;
+
+    match shown {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            log::warn!("File manager can't show {uri}, opening its folder instead: {e:?}");
+            let parent = file.parent().with_context(|| format!("{} has no parent folder", file.display()))?;
+            open(&parent.to_string_lossy())
+        }
+    }
+}
@@ -1,21 +1,75 @@
+use std::borrow::Cow;
+use std::path::PathBuf;
 use std::sync::Arc;
 use async_trait::async_trait;
-use serde_enum_str::Deserialize_enum_str;
+use serde::Serialize;
+use serde_enum_str::{Deserialize_enum_str, Serialize_enum_str};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
 
-#[derive(Debug, PartialEq, Eq, Deserialize_enum_str, Clone)]
+use crate::opener;
+
+/// An entry's type as named by doc set indexes. Parsing and serializing go through the variant
+/// names, matching [EntryType::as_str]. Parsing also takes the other names and the short codes
+/// (`instm`, `tdef`) doc sets use for the same kinds.
+#[derive(Debug, PartialEq, Eq, Deserialize_enum_str, Serialize_enum_str, Clone)]
 pub enum EntryType {
+    #[serde(alias = "cl")]
     Class,
+    #[serde(alias = "func", alias = "Procedure")]
     Function,
+    #[serde(alias = "instm", alias = "clm", alias = "intfm")]
     Method,
+    #[serde(alias = "enum", alias = "Enumeration")]
     Enum,
+    #[serde(alias = "econst", alias = "clconst", alias = "Const")]
     Constant,
+    #[serde(alias = "Setting")]
     Option,
+    #[serde(alias = "Section", alias = "Tutorial", alias = "Sample")]
     Guide,
+    #[serde(alias = "Package", alias = "Namespace", alias = "Library")]
     Module,
+    #[serde(alias = "struct", alias = "Structure", alias = "Union")]
+    Struct,
+    Trait,
+    #[serde(alias = "intf", alias = "Protocol")]
+    Interface,
+    #[serde(alias = "Property", alias = "Attribute", alias = "Member", alias = "instp", alias = "intfp")]
+    Field,
+    #[serde(alias = "var", alias = "Global")]
+    Variable,
+    #[serde(alias = "macro", alias = "Define")]
+    Macro,
+    #[serde(alias = "tdef", alias = "Typedef", alias = "Alias")]
+    Type,
     #[serde(other)]
     Other(Arc<str>),
 }
 
+impl EntryType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            EntryType::Class => "Class",
+            EntryType::Function => "Function",
+            EntryType::Method => "Method",
+            EntryType::Enum => "Enum",
+            EntryType::Constant => "Constant",
+            EntryType::Option => "Option",
+            EntryType::Guide => "Guide",
+            EntryType::Module => "Module",
+            EntryType::Struct => "Struct",
+            EntryType::Trait => "Trait",
+            EntryType::Interface => "Interface",
+            EntryType::Field => "Field",
+            EntryType::Variable => "Variable",
+            EntryType::Macro => "Macro",
+            EntryType::Type => "Type",
+            EntryType::Other(name) => name,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SearchEntry {
     pub entry_type: EntryType,
@@ -28,17 +82,342 @@ pub struct SearchEntry {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct DocSet {
     pub id: Arc<str>,
+    /// Keywords triggering this doc set, the primary one first.
     pub keywords: Vec<Arc<str>>,
     pub name: Arc<str>,
     pub description: Arc<str>,
     pub icon: Arc<str>,
+    /// Project page or source repository of what the doc set documents.
+    pub homepage: Option<Arc<str>>,
+}
+
+/// A doc set named exactly by the typed keyword is worth this much, one only matched by a
+/// keyword prefix (`rust` for `rustembedded`) at most `PREFIX_KEYWORD_RELEVANCE`, losing
+/// `KEYWORD_GAP_PENALTY` for every letter left untyped down to `MIN_KEYWORD_RELEVANCE`.
+const EXACT_KEYWORD_RELEVANCE: f64 = 1.0;
+const PREFIX_KEYWORD_RELEVANCE: f64 = 0.8;
+const KEYWORD_GAP_PENALTY: f64 = 0.02;
+const MIN_KEYWORD_RELEVANCE: f64 = 0.5;
+
+impl DocSet {
+    /// How well `keyword` picks this doc set out, `None` when none of its keywords starts with it.
+    pub fn keyword_relevance(&self, keyword: &str) -> Option<f64> {
+        let keyword = keyword.trim().to_lowercase();
+        if keyword.is_empty() {
+            return None;
+        }
+
+        self.keywords
+            .iter()
+            .map(|k| k.to_lowercase())
+            .filter(|k| k.starts_with(&keyword))
+            .map(|k| match k.chars().count() - keyword.chars().count() {
+                0 => EXACT_KEYWORD_RELEVANCE,
+                gap => (PREFIX_KEYWORD_RELEVANCE - KEYWORD_GAP_PENALTY * gap as f64).max(MIN_KEYWORD_RELEVANCE),
+            })
+            .reduce(f64::max)
+    }
 }
 
+/// Orders doc sets matched by `keyword` best match first: exact keywords, then the shortest
+/// keyword completion, then by name.
+pub fn sort_by_keyword(doc_sets: &mut [DocSet], keyword: &str) {
+    doc_sets.sort_by(|a, b| {
+        let relevance = |ds: &DocSet| ds.keyword_relevance(keyword).unwrap_or_default();
+        relevance(b).total_cmp(&relevance(a)).then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+/// Options the plugin passes down with every doc set search.
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Maximum number of entries returned for a single doc set.
+    pub limit: usize,
+    /// Only match names with the exact case of the query.
+    pub case_sensitive: bool,
+    /// Match names whatever their accents, `cafe` finding `café` and the other way around.
+    pub fold_diacritics: bool,
+    /// Entry types left out of the results. Providers should drop them before applying `limit`
+    /// so they don't take the place of shown entries.
+    pub hidden_types: Vec<EntryType>,
+    /// Every doc set is searched rather than the ones a keyword picked. Providers skip costly
+    /// work such as downloads for doc sets nobody asked for.
+    pub global: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            limit: 30,
+            case_sensitive: false,
+            fold_diacritics: false,
+            hidden_types: vec![],
+            global: false,
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Whether entries of the type named `entry_type` are left out.
+    pub fn hides(&self, entry_type: &str) -> bool {
+        self.hidden_types.iter().any(|t| t.as_str() == entry_type)
+    }
+
+    /// Folds `s` for comparison: lowercased unless in case-sensitive mode, and stripped of its
+    /// diacritics when folding those.
+    pub fn fold<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        let s = match self.fold_diacritics && !s.is_ascii() {
+            true => Cow::Owned(strip_diacritics(s)),
+            false => Cow::Borrowed(s),
+        };
+        match self.case_sensitive {
+            true => s,
+            false => Cow::Owned(s.to_lowercase()),
+        }
+    }
+}
+
+/// `s` without its diacritics, `café` becoming `cafe`: decomposed, with the combining marks
+/// dropped.
+pub fn strip_diacritics(s: &str) -> String {
+    s.nfd().filter(|c| !is_combining_mark(*c)).collect()
+}
+
+/// Scores `name` against the query the same way the Dash relevance buckets do, for providers
+/// matching names in memory. Both sides are expected to be folded with [SearchOptions::fold].
+pub fn name_relevance(name_lc: &str, q_lc: &str) -> Option<usize> {
+    if name_lc == q_lc {
+        Some(100)
+    } else if name_lc.starts_with(q_lc) {
+        Some(80)
+    } else if name_lc.contains(q_lc) {
+        Some(50)
+    } else {
+        None
+    }
+}
+
+/// Health summary of a provider, reported through the control interface.
+#[derive(Debug, Serialize, Default)]
+pub struct ProviderStatus {
+    pub provider: String,
+    pub roots: Vec<PathBuf>,
+    pub loaded: usize,
+    pub skipped: usize,
+    pub doc_sets: Vec<DocSetStatus>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DocSetStatus {
+    pub id: String,
+    pub healthy: bool,
+    pub error: Option<String>,
+}
+
+/// Integrity report of one doc set, see [DocProvider::check].
+#[derive(Debug, Serialize)]
+pub struct DocSetCheck {
+    pub id: String,
+    pub healthy: bool,
+    pub checks: Vec<Check>,
+}
+
+/// A single check run on a doc set, with what's wrong when it failed.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub error: Option<String>,
+}
+
+impl DocSetCheck {
+    pub fn new(id: impl Into<String>, checks: Vec<Check>) -> Self {
+        Self {
+            id: id.into(),
+            healthy: checks.iter().all(|c| c.error.is_none()),
+            checks,
+        }
+    }
+}
+
+pub type DynProvider = Arc<dyn DocProvider + Send + Sync + 'static>;
+
 #[async_trait]
 pub trait DocProvider {
     fn name(&self) -> &str;
+
+    /// Icon standing for the provider as a whole, shown on entries tied to the provider rather
+    /// than to one of its entries, and on doc sets without an icon of their own.
+    fn icon(&self) -> &str {
+        "documentation"
+    }
+
+    /// Lists every doc set this provider knows about, regardless of keywords.
+    async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>>;
+
+    /// Finds the doc sets triggered by `keyword`. A doc set matches when the typed keyword is a
+    /// case-insensitive prefix of one of its keywords, never the other way around.
     async fn search_doc_sets(&self, keyword: &str) -> anyhow::Result<Vec<DocSet>>;
-    async fn search(&self, doc_set_id: &str, q: &str) -> anyhow::Result<Vec<SearchEntry>>;
+
+    /// Cheap check run before anything is spawned for a query: `false` means no doc set of this
+    /// provider can match `keyword`. Answered from memory, without I/O. Providers that know their
+    /// keywords should answer it exactly, it also decides whether a first word is a keyword at all.
+    fn supports_keyword(&self, _keyword: &str) -> bool {
+        true
+    }
+    async fn search(&self, doc_set_id: &str, q: &str, options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>>;
     async fn open(&self, doc_set_id: &str, entry_url: &str) -> anyhow::Result<()>;
-    async fn clean_up(&self);
+
+    /// The file on disk behind an entry, for actions that work on the file rather than the page.
+    async fn entry_path(&self, doc_set_id: &str, entry_url: &str) -> anyhow::Result<PathBuf> {
+        anyhow::bail!("{} entry {doc_set_id}/{entry_url} has no file on disk", self.name())
+    }
+
+    /// The URL an entry opens, for callers presenting entries themselves. Defaults to a `file://`
+    /// URL of [DocProvider::entry_path], `None` when the entry isn't opened as a page.
+    async fn resolve_url(&self, doc_set_id: &str, entry_url: &str) -> Option<String> {
+        let path = self.entry_path(doc_set_id, entry_url).await.ok()?;
+        Some(opener::file_url(&path))
+    }
+
+    /// Opens the landing page of a doc set, used when a keyword completion is selected.
+    /// Providers without such a page do nothing.
+    async fn open_doc_set(&self, doc_set_id: &str) -> anyhow::Result<()> {
+        log::debug!("Provider {} has no landing page for {doc_set_id}", self.name());
+        Ok(())
+    }
+
+    /// Reports what this provider has loaded. Providers without a notion of health report
+    /// every listed doc set as healthy.
+    async fn status(&self) -> anyhow::Result<ProviderStatus> {
+        let doc_sets = self.doc_sets().await?;
+        Ok(ProviderStatus {
+            provider: self.name().to_string(),
+            loaded: doc_sets.len(),
+            doc_sets: doc_sets
+                .into_iter()
+                .map(|ds| DocSetStatus {
+                    id: ds.id.to_string(),
+                    healthy: true,
+                    error: None,
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Looks every loaded doc set over for what would make it return nothing, such as a broken
+    /// index or a missing documents folder. Providers without anything to check report each
+    /// doc set healthy.
+    async fn check(&self) -> anyhow::Result<Vec<DocSetCheck>> {
+        let doc_sets = self.doc_sets().await?;
+        Ok(doc_sets.into_iter().map(|ds| DocSetCheck::new(ds.id.as_ref(), vec![])).collect())
+    }
+
+    /// Refreshes what the provider has loaded, e.g. rescanning its folders, asked for by the
+    /// `Reload` control method. Providers with nothing to refresh do nothing.
+    async fn reload(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Releases what can be rebuilt: KRunner calls `Teardown` when its window closes and keeps
+    /// sending `Match` calls to the same object afterwards. The provider must still answer them,
+    /// re-opening whatever it let go of on demand, so nothing here may be permanent.
+    async fn clean_up(&self) -> anyhow::Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn doc_set(keywords: &[&str]) -> DocSet {
+        DocSet {
+            id: "python".into(),
+            keywords: keywords.iter().map(|k| Arc::from(*k)).collect(),
+            name: "Python".into(),
+            description: Arc::from(""),
+            icon: Arc::from(""),
+            homepage: None,
+        }
+    }
+
+    #[test]
+    fn keyword_relevance_only_matches_typed_prefixes() {
+        let python = doc_set(&["python", "py3"]);
+
+        assert_eq!(python.keyword_relevance("python"), Some(EXACT_KEYWORD_RELEVANCE));
+        assert_eq!(python.keyword_relevance(" PY3 "), Some(EXACT_KEYWORD_RELEVANCE));
+        // `py` is a prefix of both keywords, the closer one counts
+        assert_eq!(python.keyword_relevance("py"), Some(PREFIX_KEYWORD_RELEVANCE - KEYWORD_GAP_PENALTY));
+        assert_eq!(python.keyword_relevance("pythonista"), None);
+        assert_eq!(python.keyword_relevance(""), None);
+    }
+
+    /// Every variant but `Other`.
+    fn entry_types() -> Vec<EntryType> {
+        // Stops compiling when a variant is added, until it's listed below too
+        match EntryType::Class {
+            EntryType::Class | EntryType::Function | EntryType::Method | EntryType::Enum | EntryType::Constant
+            | EntryType::Option | EntryType::Guide | EntryType::Module | EntryType::Struct | EntryType::Trait
+            | EntryType::Interface | EntryType::Field | EntryType::Variable | EntryType::Macro | EntryType::Type
+            | EntryType::Other(_) => {}
+        }
+        vec![
+            EntryType::Class, EntryType::Function, EntryType::Method, EntryType::Enum, EntryType::Constant,
+            EntryType::Option, EntryType::Guide, EntryType::Module, EntryType::Struct, EntryType::Trait,
+            EntryType::Interface, EntryType::Field, EntryType::Variable, EntryType::Macro, EntryType::Type,
+        ]
+    }
+
+    #[test]
+    fn entry_types_round_trip_through_their_names() {
+        for entry_type in entry_types() {
+            assert_eq!(entry_type.as_str().parse::<EntryType>().unwrap(), entry_type);
+            assert_eq!(entry_type.to_string(), entry_type.as_str());
+        }
+    }
+
+    #[test]
+    fn every_entry_type_has_an_icon() {
+        let fallback = EntryType::Other("Keyword".into()).get_krunner_icon(&HashMap::new());
+        for entry_type in entry_types() {
+            assert_ne!(entry_type.get_krunner_icon(&HashMap::new()), fallback, "{entry_type:?}");
+        }
+    }
+
+    #[test]
+    fn parses_the_type_names_doc_sets_use() {
+        let samples = [
+            ("Struct", EntryType::Struct),
+            ("struct", EntryType::Struct),
+            ("Union", EntryType::Struct),
+            ("Trait", EntryType::Trait),
+            ("Protocol", EntryType::Interface),
+            ("intf", EntryType::Interface),
+            ("Property", EntryType::Field),
+            ("instp", EntryType::Field),
+            ("Global", EntryType::Variable),
+            ("Define", EntryType::Macro),
+            ("tdef", EntryType::Type),
+            ("Typedef", EntryType::Type),
+            ("instm", EntryType::Method),
+            ("clm", EntryType::Method),
+            ("func", EntryType::Function),
+            ("econst", EntryType::Constant),
+            ("Namespace", EntryType::Module),
+            ("Section", EntryType::Guide),
+            ("Keyword", EntryType::Other("Keyword".into())),
+        ];
+        for (name, entry_type) in samples {
+            assert_eq!(name.parse::<EntryType>().unwrap(), entry_type, "parsing {name}");
+        }
+    }
+
+    #[test]
+    fn keyword_relevance_bottoms_out() {
+        let doc_set = doc_set(&["rustembeddedbookdiscoverybookandnomicon"]);
+        assert_eq!(doc_set.keyword_relevance("r"), Some(MIN_KEYWORD_RELEVANCE));
+    }
 }
@@ -0,0 +1,237 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use flate2::read::ZlibDecoder;
+use tokio::task::spawn_blocking;
+
+use crate::config::PyDocConfig;
+use crate::logging::debug_throttled;
+use crate::opener;
+use crate::provider::{name_relevance, DocProvider, DocSet, EntryType, SearchEntry, SearchOptions};
+
+const DOC_SET_ID: &str = "pydoc";
+/// The Sphinx inventory listing every documented object, shipped at the root of the docs.
+const INVENTORY_FILE: &str = "objects.inv";
+
+/// Inventory roles indexed, with the entry type they map to. Labels and the like are left out,
+/// they'd bury the symbols.
+const ROLES: &[(&str, EntryType)] = &[
+    ("py:module", EntryType::Module),
+    ("py:class", EntryType::Class),
+    ("py:exception", EntryType::Class),
+    ("py:function", EntryType::Function),
+    ("py:method", EntryType::Method),
+    ("py:classmethod", EntryType::Method),
+    ("py:staticmethod", EntryType::Method),
+    ("py:attribute", EntryType::Field),
+    ("py:property", EntryType::Field),
+    ("py:data", EntryType::Variable),
+    ("std:doc", EntryType::Guide),
+];
+
+struct PyDocEntry {
+    name: Arc<str>,
+    entry_type: EntryType,
+    /// Page and anchor relative to the docs root.
+    uri: Arc<str>,
+}
+
+/// Serves a locally installed copy of the CPython HTML docs (e.g. Debian's `python3-doc`) as a
+/// single doc set, indexed from the docs' Sphinx inventory.
+pub struct PyDoc {
+    root: PathBuf,
+    keyword: String,
+    entries: Vec<PyDocEntry>,
+}
+
+impl PyDoc {
+    pub async fn new(config: &PyDocConfig) -> anyhow::Result<Self> {
+        let root = config.dirs.iter().find(|d| d.join(INVENTORY_FILE).is_file()).cloned();
+        let entries = match &root {
+            Some(root) => {
+                let inventory = root.join(INVENTORY_FILE);
+                spawn_blocking(move || read_inventory(&inventory))
+                    .await?
+                    .with_context(|| format!("Reading the Python docs inventory in {}", root.display()))?
+            }
+            None => {
+                log::debug!("No Python docs found in {:?}", config.dirs);
+                vec![]
+            }
+        };
+        log::debug!("Found {} Python doc entries", entries.len());
+
+        Ok(Self {
+            root: root.unwrap_or_default(),
+            keyword: config.keyword.to_lowercase(),
+            entries,
+        })
+    }
+
+    fn to_doc_set(&self) -> DocSet {
+        DocSet {
+            id: DOC_SET_ID.into(),
+            keywords: vec![self.keyword.as_str().into()],
+            name: Arc::from("Python"),
+            description: Arc::from("Python documentation"),
+            icon: Arc::from("text-x-python"),
+            homepage: None,
+        }
+    }
+}
+
+/// Parses a version 2 Sphinx inventory: four header lines, then zlib compressed lines of
+/// `name domain:role priority uri display-name`. Names may contain spaces.
+fn read_inventory(file: &Path) -> anyhow::Result<Vec<PyDocEntry>> {
+    let mut reader = BufReader::new(std::fs::File::open(file)?);
+    let mut header = String::new();
+    reader.read_line(&mut header)?;
+    anyhow::ensure!(header.contains("version 2"), "Unsupported inventory format: {}", header.trim());
+    for _ in 0..3 {
+        reader.read_line(&mut header)?;
+    }
+
+    let mut body = String::new();
+    ZlibDecoder::new(reader).read_to_string(&mut body).context("Decompressing inventory")?;
+    Ok(body.lines().filter_map(parse_inventory_line).collect())
+}
+
+fn parse_inventory_line(line: &str) -> Option<PyDocEntry> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let role_at = (1..tokens.len().saturating_sub(2))
+        .find(|&i| tokens[i].contains(':') && tokens[i + 1].parse::<i32>().is_ok())?;
+    let entry_type = ROLES.iter().find(|(role, _)| *role == tokens[role_at])?.1.clone();
+    let name = tokens[..role_at].join(" ");
+    // A trailing `$` abbreviates the object's name
+    let uri = match tokens[role_at + 2].strip_suffix('$') {
+        Some(prefix) => format!("{prefix}{name}"),
+        None => tokens[role_at + 2].to_string(),
+    };
+
+    Some(PyDocEntry {
+        name: name.into(),
+        entry_type,
+        uri: uri.into(),
+    })
+}
+
+#[async_trait]
+impl DocProvider for PyDoc {
+    fn name(&self) -> &str {
+        "pydoc"
+    }
+
+    fn icon(&self) -> &str {
+        "text-x-python"
+    }
+
+    async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>> {
+        if self.entries.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![self.to_doc_set()])
+    }
+
+    async fn search_doc_sets(&self, keyword: &str) -> anyhow::Result<Vec<DocSet>> {
+        if !self.supports_keyword(keyword) {
+            return Ok(vec![]);
+        }
+        self.doc_sets().await
+    }
+
+    fn supports_keyword(&self, keyword: &str) -> bool {
+        let keyword = keyword.trim().to_lowercase();
+        !keyword.is_empty() && self.keyword.starts_with(&keyword)
+    }
+
+    async fn search(&self, doc_set_id: &str, q: &str, options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>> {
+        if doc_set_id != DOC_SET_ID {
+            return Ok(vec![]);
+        }
+
+        let q = options.fold(q.trim());
+        let mut rs: Vec<SearchEntry> = self.entries
+            .iter()
+            .filter(|e| !options.hides(e.entry_type.as_str()))
+            .filter_map(|e| {
+                let relevance = name_relevance(&options.fold(&e.name), &q)?;
+                Some(SearchEntry {
+                    entry_type: e.entry_type.clone(),
+                    title: e.name.clone(),
+                    desc: Arc::from(e.entry_type.as_str()),
+                    id: e.uri.clone(),
+                    relevance,
+                })
+            })
+            .collect();
+
+        rs.sort_by(|a, b| b.relevance.cmp(&a.relevance).then(a.title.len().cmp(&b.title.len())));
+        rs.truncate(options.limit);
+        debug_throttled!("Searching pydoc for {q} got {} results", rs.len());
+        Ok(rs)
+    }
+
+    async fn open(&self, _doc_set_id: &str, entry_url: &str) -> anyhow::Result<()> {
+        let url = match entry_url.split_once('#') {
+            Some((page, fragment)) => format!("{}#{fragment}", opener::file_url(&self.root.join(page))),
+            None => opener::file_url(&self.root.join(entry_url)),
+        };
+        opener::open(&url)
+    }
+
+    async fn entry_path(&self, _doc_set_id: &str, entry_url: &str) -> anyhow::Result<PathBuf> {
+        let page = entry_url.split_once('#').map_or(entry_url, |(page, _)| page);
+        Ok(self.root.join(page))
+    }
+
+    async fn open_doc_set(&self, _doc_set_id: &str) -> anyhow::Result<()> {
+        opener::open(&opener::file_url(&self.root.join("index.html")))
+    }
+
+    async fn clean_up(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Option<(String, EntryType, String)> {
+        parse_inventory_line(line).map(|e| (e.name.to_string(), e.entry_type, e.uri.to_string()))
+    }
+
+    #[test]
+    fn expands_abbreviated_uris() {
+        assert_eq!(
+            parse("os.path.join py:function 1 library/os.path.html#$ -"),
+            Some(("os.path.join".into(), EntryType::Function, "library/os.path.html#os.path.join".into())),
+        );
+        assert_eq!(
+            parse("os.path py:module 0 library/os.path.html#module-$ -"),
+            Some(("os.path".into(), EntryType::Module, "library/os.path.html#module-os.path".into())),
+        );
+    }
+
+    #[test]
+    fn keeps_names_with_spaces_whole() {
+        assert_eq!(
+            parse("Data model py:class 1 reference/datamodel.html#$ -"),
+            Some(("Data model".into(), EntryType::Class, "reference/datamodel.html#Data model".into())),
+        );
+        // The display name may have spaces, numbers and colons too
+        assert_eq!(
+            parse("whatsnew/3.12 std:doc -1 whatsnew/3.12.html What's New In Python 3.12: 1 summary"),
+            Some(("whatsnew/3.12".into(), EntryType::Guide, "whatsnew/3.12.html".into())),
+        );
+    }
+
+    #[test]
+    fn skips_roles_that_arent_indexed() {
+        assert_eq!(parse("tut-intro std:label -1 tutorial/introduction.html#$ An Informal Introduction"), None);
+        assert_eq!(parse("# Sphinx inventory version 2"), None);
+    }
+}
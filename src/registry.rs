@@ -0,0 +1,143 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+
+use crate::config::Config;
+use crate::dash::Dash;
+use crate::devdocs::DevDocs;
+use crate::error::DocError;
+use crate::godoc::GoDoc;
+use crate::infopages::InfoPages;
+use crate::markdown::Markdown;
+use crate::provider::DynProvider;
+use crate::pydoc::PyDoc;
+use crate::tldr::Tldr;
+
+type ProviderFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<DynProvider>> + Send + 'a>>;
+type ProviderConstructor = fn(&Config) -> ProviderFuture<'_>;
+
+/// Every provider that can be enabled from the config, by name.
+const PROVIDERS: &[(&str, ProviderConstructor)] = &[
+    ("Dash", dash),
+    ("tldr", tldr),
+    ("info", info),
+    ("Markdown", markdown),
+    ("DevDocs", devdocs),
+    ("pydoc", pydoc),
+    ("godoc", godoc),
+];
+
+fn dash(config: &Config) -> ProviderFuture<'_> {
+    Box::pin(async move { Ok(Arc::new(Dash::new(&config.dash).await?) as DynProvider) })
+}
+
+fn tldr(_config: &Config) -> ProviderFuture<'_> {
+    Box::pin(async move { Ok(Arc::new(Tldr::new_with_default().await?) as DynProvider) })
+}
+
+fn info(config: &Config) -> ProviderFuture<'_> {
+    Box::pin(async move { Ok(Arc::new(InfoPages::new(&config.info).await?) as DynProvider) })
+}
+
+fn markdown(config: &Config) -> ProviderFuture<'_> {
+    Box::pin(async move { Ok(Arc::new(Markdown::new(config.markdown.roots.clone()).await?) as DynProvider) })
+}
+
+fn devdocs(config: &Config) -> ProviderFuture<'_> {
+    Box::pin(async move { Ok(Arc::new(DevDocs::new(&config.devdocs)?) as DynProvider) })
+}
+
+fn pydoc(config: &Config) -> ProviderFuture<'_> {
+    Box::pin(async move { Ok(Arc::new(PyDoc::new(&config.pydoc).await?) as DynProvider) })
+}
+
+fn godoc(config: &Config) -> ProviderFuture<'_> {
+    Box::pin(async move { Ok(Arc::new(GoDoc::new(&config.godoc).await) as DynProvider) })
+}
+
+/// Builds the providers enabled in `config`, in config order. Providers found in `existing`
+/// are reused as they are, so a reload only constructs the newly enabled ones.
+pub async fn build_providers(config: &Config, existing: &[DynProvider]) -> anyhow::Result<Vec<DynProvider>> {
+    let mut providers = vec![];
+    for name in &config.providers {
+        if let Some(p) = existing.iter().find(|p| p.name() == name) {
+            providers.push(p.clone());
+            continue;
+        }
+
+        match PROVIDERS.iter().find(|(n, _)| n == name) {
+            Some((_, constructor)) => {
+                log::debug!("Creating provider {name}");
+                providers.push(constructor(config).await?);
+            }
+            None => log::warn!("Ignoring unknown provider {name}, available: {:?}", PROVIDERS.iter().map(|(n, _)| n).collect::<Vec<_>>()),
+        }
+    }
+    Ok(providers)
+}
+
+/// Logs the doc sets failing `DocProvider::check`, for spotting broken ones while debugging.
+pub async fn log_checks(providers: &[DynProvider]) {
+    for p in providers {
+        let checks = match p.check().await {
+            Ok(v) => v,
+            Err(e) => {
+                log::debug!("Unable to check the doc sets of {}: {e:?}", p.name());
+                continue;
+            }
+        };
+        for ds in checks.iter().filter(|ds| !ds.healthy) {
+            for check in &ds.checks {
+                if let Some(error) = &check.error {
+                    log::debug!("Doc set {}/{} failed the {} check: {error}", p.name(), ds.id, check.name);
+                }
+            }
+        }
+        log::debug!("{}: {} of {} doc sets healthy", p.name(), checks.iter().filter(|ds| ds.healthy).count(), checks.len());
+    }
+}
+
+/// The providers currently serving queries, shared by the KRunner and control interfaces and
+/// replaced as a whole on reload.
+#[derive(Clone, Default)]
+pub struct ProviderSet(Arc<RwLock<Arc<Vec<DynProvider>>>>);
+
+impl ProviderSet {
+    /// Fails when two providers share a name, entries are routed back to their provider by name.
+    pub fn new(providers: Vec<DynProvider>) -> anyhow::Result<Self> {
+        check_unique_names(&providers)?;
+        Ok(Self(Arc::new(RwLock::new(Arc::new(providers)))))
+    }
+
+    pub fn snapshot(&self) -> Arc<Vec<DynProvider>> {
+        self.0.read().unwrap().clone()
+    }
+
+    pub fn find(&self, name: &str) -> Option<DynProvider> {
+        self.snapshot().iter().find(|p| p.name() == name).cloned()
+    }
+
+    /// Like [ProviderSet::find], for callers reporting a missing provider back to the user.
+    pub fn require(&self, name: &str) -> Result<DynProvider, DocError> {
+        self.find(name).ok_or_else(|| DocError::ProviderNotFound(name.to_string()))
+    }
+
+    pub fn replace(&self, providers: Vec<DynProvider>) -> anyhow::Result<()> {
+        check_unique_names(&providers)?;
+        *self.0.write().unwrap() = Arc::new(providers);
+        Ok(())
+    }
+}
+
+fn check_unique_names(providers: &[DynProvider]) -> anyhow::Result<()> {
+    let mut duplicates: Vec<&str> = providers
+        .iter()
+        .enumerate()
+        .filter(|(i, p)| providers[..*i].iter().any(|other| other.name() == p.name()))
+        .map(|(_, p)| p.name())
+        .collect();
+    duplicates.sort_unstable();
+    duplicates.dedup();
+    anyhow::ensure!(duplicates.is_empty(), "Providers with duplicate names: {duplicates:?}");
+    Ok(())
+}
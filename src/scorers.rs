@@ -0,0 +1,56 @@
+use std::fmt::Debug;
+
+use crate::provider::SearchEntry;
+
+/// Re-ranks the entries a doc set returned for a query, for doc sets the generic relevance SQL
+/// doesn't suit.
+pub trait DocSetScorer: Debug + Send + Sync {
+    /// Adjusts the relevance of `entries` found for `q`, callers re-sort them afterwards.
+    fn rescore(&self, q: &str, entries: &mut [SearchEntry]);
+}
+
+/// Keeps the SQL ranking as it is, used by every doc set without a dedicated scorer.
+#[derive(Debug)]
+pub struct PassThrough;
+
+impl DocSetScorer for PassThrough {
+    fn rescore(&self, _q: &str, _entries: &mut [SearchEntry]) {}
+}
+
+/// Doc sets with a dedicated scorer, by doc set name.
+const SCORERS: &[(&str, &dyn DocSetScorer)] = &[
+    ("C++", &CppReference),
+];
+
+/// Picks the scorer registered for the doc set `name`, [PassThrough] when there's none.
+pub fn scorer_for(name: &str) -> &'static dyn DocSetScorer {
+    SCORERS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map_or(&PassThrough as &dyn DocSetScorer, |(_, scorer)| *scorer)
+}
+
+/// cppreference lists symbols qualified (`std::vector`) next to implementation details such as
+/// `std::__1::vector` or `__gnu_cxx::` helpers, which the generic SQL ranks alike since the
+/// query only ever matches in the middle of them. Public `std::` symbols named exactly by the
+/// query go to the top, reserved (`_`-prefixed) namespaces to the bottom.
+#[derive(Debug)]
+struct CppReference;
+
+impl DocSetScorer for CppReference {
+    fn rescore(&self, q: &str, entries: &mut [SearchEntry]) {
+        let q = q.trim().to_lowercase();
+        for entry in entries {
+            let title = entry.title.to_lowercase();
+            let (namespaces, unqualified) = title.rsplit_once("::").unwrap_or(("", &title));
+
+            if namespaces.split("::").any(|ns| ns.starts_with('_')) {
+                entry.relevance /= 4;
+            } else if title.starts_with("std::") && unqualified == q {
+                entry.relevance = 100;
+            } else if title.starts_with("std::") {
+                entry.relevance = (entry.relevance + 10).min(100);
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use tokio::task::spawn_blocking;
+
+use crate::logging::debug_throttled;
+use crate::opener;
+use crate::provider::{name_relevance, DocProvider, DocSet, EntryType, SearchEntry, SearchOptions};
+
+const DOC_SET_ID: &str = "tldr";
+/// Platform folders in order of preference: a page found in an earlier folder shadows the
+/// same page from later ones.
+const PLATFORMS: &[&str] = &["linux", "common", "osx", "windows", "android", "sunos"];
+
+/// Serves the pages cached by a tldr client as a single `tldr` doc set.
+pub struct Tldr {
+    pages: Vec<(Arc<str>, PathBuf)>,
+}
+
+impl Tldr {
+    pub async fn new_with_default() -> anyhow::Result<Self> {
+        let roots: Vec<PathBuf> = [
+            dirs::cache_dir().map(|d| d.join("tldr").join("pages")),
+            dirs::data_dir().map(|d| d.join("tldr").join("pages")),
+            dirs::home_dir().map(|d| d.join(".tldr").join("cache").join("pages")),
+        ]
+            .into_iter()
+            .flatten()
+            .filter(|p| p.is_dir())
+            .collect();
+
+        log::debug!("Indexing tldr pages in {roots:?}");
+        let pages = spawn_blocking(move || index_pages(&roots)).await.context("Indexing tldr pages")?;
+        log::debug!("Found {} tldr pages", pages.len());
+        Ok(Self { pages })
+    }
+
+    fn to_doc_set(&self) -> DocSet {
+        DocSet {
+            id: DOC_SET_ID.into(),
+            keywords: vec![DOC_SET_ID.into()],
+            name: DOC_SET_ID.into(),
+            description: Arc::from("tldr pages"),
+            icon: Arc::from("utilities-terminal"),
+            homepage: None,
+        }
+    }
+}
+
+fn index_pages(roots: &[PathBuf]) -> Vec<(Arc<str>, PathBuf)> {
+    let mut pages: HashMap<String, PathBuf> = HashMap::new();
+    for platform in PLATFORMS {
+        for root in roots {
+            let entries = match std::fs::read_dir(root.join(platform)) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+
+            for path in entries.flatten().map(|e| e.path()) {
+                if path.extension().is_none_or(|ext| ext != "md") {
+                    continue;
+                }
+
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                    pages.entry(name.to_string()).or_insert(path);
+                }
+            }
+        }
+    }
+
+    let mut pages: Vec<(Arc<str>, PathBuf)> = pages.into_iter().map(|(name, path)| (name.into(), path)).collect();
+    pages.sort();
+    pages
+}
+
+#[async_trait]
+impl DocProvider for Tldr {
+    fn name(&self) -> &str {
+        "tldr"
+    }
+
+    fn icon(&self) -> &str {
+        "utilities-terminal"
+    }
+
+    async fn doc_sets(&self) -> anyhow::Result<Vec<DocSet>> {
+        if self.pages.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![self.to_doc_set()])
+    }
+
+    async fn search_doc_sets(&self, keyword: &str) -> anyhow::Result<Vec<DocSet>> {
+        if !self.supports_keyword(keyword) {
+            return Ok(vec![]);
+        }
+        self.doc_sets().await
+    }
+
+    fn supports_keyword(&self, keyword: &str) -> bool {
+        let keyword = keyword.trim().to_lowercase();
+        !keyword.is_empty() && DOC_SET_ID.starts_with(&keyword)
+    }
+
+    async fn search(&self, doc_set_id: &str, q: &str, options: &SearchOptions) -> anyhow::Result<Vec<SearchEntry>> {
+        if doc_set_id != DOC_SET_ID {
+            return Ok(vec![]);
+        }
+
+        let q = options.fold(q.trim());
+        let mut rs: Vec<SearchEntry> = self.pages
+            .iter()
+            .filter_map(|(name, _)| {
+                let relevance = name_relevance(&options.fold(name), &q)?;
+                Some(SearchEntry {
+                    entry_type: EntryType::Guide,
+                    title: name.clone(),
+                    desc: Arc::from(""),
+                    id: name.clone(),
+                    relevance,
+                })
+            })
+            .collect();
+
+        rs.sort_by(|a, b| b.relevance.cmp(&a.relevance).then(a.title.len().cmp(&b.title.len())));
+        rs.truncate(options.limit);
+        debug_throttled!("Searching tldr for {q} got {} results", rs.len());
+        Ok(rs)
+    }
+
+    async fn open(&self, doc_set_id: &str, entry_url: &str) -> anyhow::Result<()> {
+        let path = self.entry_path(doc_set_id, entry_url).await?;
+        opener::open(&path.to_string_lossy())
+    }
+
+    async fn entry_path(&self, _doc_set_id: &str, entry_url: &str) -> anyhow::Result<PathBuf> {
+        self.pages
+            .iter()
+            .find(|(name, _)| name.as_ref() == entry_url)
+            .map(|(_, path)| path.clone())
+            .with_context(|| format!("Unknown tldr page {entry_url}"))
+    }
+
+    async fn clean_up(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
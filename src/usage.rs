@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+const SECS_PER_DAY: f64 = 86400.0;
+
+/// How often and how recently entries of each doc set were opened, kept under the state dir so
+/// keyword completions can list the doc sets in use first. Does nothing without a file.
+#[derive(Default)]
+pub struct Usage {
+    file: Option<PathBuf>,
+    stats: Mutex<HashMap<String, DocSetUsage>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+struct DocSetUsage {
+    opens: u64,
+    /// Seconds since the epoch.
+    last_opened: u64,
+}
+
+impl Usage {
+    /// Reads the stats kept in `file`, `None` turns tracking off.
+    pub fn load(file: Option<PathBuf>) -> Self {
+        let stats = match &file {
+            Some(file) => match std::fs::read(file) {
+                Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                    log::warn!("Ignoring unreadable usage stats {}: {e:?}", file.display());
+                    Default::default()
+                }),
+                Err(e) => {
+                    log::debug!("No usage stats at {}: {e:?}", file.display());
+                    Default::default()
+                }
+            },
+            None => Default::default(),
+        };
+        Self { file, stats: Mutex::new(stats) }
+    }
+
+    /// Between 0 for a doc set never opened and 1 for one opened often and lately. Opens count
+    /// less the more days have gone by since the last one.
+    pub fn score(&self, provider: &str, doc_set_id: &str) -> f64 {
+        let usage = match self.stats.lock().unwrap().get(&key(provider, doc_set_id)) {
+            Some(v) => *v,
+            None => return 0.0,
+        };
+        let days = now().saturating_sub(usage.last_opened) as f64 / SECS_PER_DAY;
+        let frecency = usage.opens as f64 / (1.0 + days);
+        frecency / (frecency + 1.0)
+    }
+
+    /// Counts an entry of the doc set as opened and saves the stats.
+    pub async fn record(&self, provider: &str, doc_set_id: &str) -> anyhow::Result<()> {
+        let file = match &self.file {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+
+        let data = {
+            let mut stats = self.stats.lock().unwrap();
+            let usage = stats.entry(key(provider, doc_set_id)).or_default();
+            usage.opens += 1;
+            usage.last_opened = now();
+            serde_json::to_vec(&*stats)?
+        };
+        if let Some(dir) = file.parent() {
+            tokio::fs::create_dir_all(dir).await.with_context(|| format!("Creating {}", dir.display()))?;
+        }
+        tokio::fs::write(file, data).await
+            .with_context(|| format!("Writing {}", file.display()))
+    }
+}
+
+fn key(provider: &str, doc_set_id: &str) -> String {
+    format!("{provider}/{doc_set_id}")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+/// Bursts of file events (an editor saving, a `git pull`) are folded into one change.
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// Watches `paths` recursively. The returned receiver yields once per settled burst of changes
+/// and closes when the watcher is dropped.
+pub fn watch(paths: &[PathBuf]) -> anyhow::Result<(RecommendedWatcher, UnboundedReceiver<()>)> {
+    let (raw_tx, mut raw_rx) = unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) if !event.kind.is_access() => {
+                let _ = raw_tx.send(());
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("File watcher error: {e:?}"),
+        }
+    }).context("Creating file watcher")?;
+
+    for path in paths {
+        watcher.watch(path, RecursiveMode::Recursive)
+            .with_context(|| format!("Watching {}", path.display()))?;
+    }
+
+    let (tx, rx) = unbounded_channel();
+    tokio::spawn(async move {
+        while raw_rx.recv().await.is_some() {
+            tokio::time::sleep(SETTLE_DELAY).await;
+            while raw_rx.try_recv().is_ok() {}
+            if tx.send(()).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok((watcher, rx))
+}